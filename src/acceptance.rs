@@ -0,0 +1,326 @@
+use ndarray::Array2;
+use serde::{Serialize, Deserialize};
+
+use crate::defect::{self, DefectConfig, Indication};
+use crate::gate::{Gate, GateConfig};
+use crate::roi::Roi;
+use crate::signal::{AmplitudeMode, DecibelReference};
+use crate::thickness::ThicknessConfig;
+
+/// Acceptance criteria of a component
+///
+/// Each rule group is optional, but at least one has to be set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AcceptanceProfile {
+    /// Name of the profile, e.g. the part number
+    pub name: String,
+    /// Rules for the indications of the C-Scan
+    pub indications: Option<IndicationRules>,
+    /// Minimum wall thickness
+    pub thickness: Option<ThicknessRule>,
+    /// Maximum loss of the back-wall echo
+    pub backwall: Option<BackwallRule>,
+    /// Reference of the dB values of the C-Scan and the back-wall amplitude
+    #[serde(default)]
+    pub db_reference: DecibelReference
+}
+
+/// Rules for the indications detected on the C-Scan
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndicationRules {
+    /// Aperture of the C-Scan
+    pub gate: Gate,
+    /// Amplitude measure inside the aperture
+    #[serde(default)]
+    pub mode: AmplitudeMode,
+    /// Segmentation of the C-Scan in dB
+    #[serde(flatten)]
+    pub detection: DefectConfig,
+    /// Maximum -6 dB length or width of an indication in mm
+    pub max_size: Option<f64>,
+    /// Maximum area of an indication in mm²
+    pub max_area: Option<f64>,
+    /// Zones with a maximum cumulated indication area
+    #[serde(default)]
+    pub zones: Vec<AcceptanceZone>
+}
+
+/// Region with a limited cumulated indication area
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AcceptanceZone {
+    /// Name of the zone
+    pub name: String,
+    /// Region of the zone
+    pub roi: Roi,
+    /// Maximum cumulated area of the indications inside the zone in mm²
+    pub max_area: f64
+}
+
+/// Minimum wall thickness measured with the named gates
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThicknessRule {
+    /// Wall thickness measurement
+    #[serde(flatten)]
+    pub config: ThicknessConfig,
+    /// Minimum wall thickness in mm
+    pub min: f64
+}
+
+/// Maximum loss of the back-wall echo measured with a named gate
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackwallRule {
+    /// Name of the gate measuring the back-wall echo
+    pub gate: String,
+    /// Reference level in dB (default: maximum of the back-wall amplitude)
+    pub reference: Option<f64>,
+    /// Maximum drop below the reference level in dB
+    pub max_loss: f64
+}
+
+/// Rule of an acceptance profile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcceptanceRule {
+    /// Maximum -6 dB size of an indication
+    IndicationSize,
+    /// Maximum area of an indication
+    IndicationArea,
+    /// Maximum cumulated indication area of a zone
+    ZoneArea,
+    /// Minimum wall thickness
+    MinThickness,
+    /// Maximum back-wall loss
+    BackwallLoss
+}
+
+/// Violated rule of an acceptance profile
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Violation {
+    /// Violated rule
+    pub rule: AcceptanceRule,
+    /// Name of the zone for zone rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    /// Limit of the rule
+    pub limit: f64,
+    /// Worst measured value, `null` if only datapoints without a measurement violate the rule
+    pub value: f64,
+    /// Area of the violating datapoints in mm² for thickness and back-wall rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub area: Option<f64>,
+    /// Part of `area` without a detected echo in mm²
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_area: Option<f64>,
+    /// Violating indications
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indications: Vec<Indication>
+}
+
+/// Result of the evaluation of an acceptance profile
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AcceptanceResult {
+    /// Name of the evaluated profile
+    pub profile: String,
+    /// All rules are fulfilled
+    pub passed: bool,
+    /// Absolute segmentation threshold in dB if indication rules are set
+    pub threshold: Option<f64>,
+    /// Number of detected indications
+    pub indication_count: usize,
+    /// Violated rules
+    pub violations: Vec<Violation>
+}
+
+/// Scans of a channel the acceptance rules are evaluated on
+pub struct AcceptanceScans<'a> {
+    /// C-Scan of the indication rules in dB
+    pub c_scan_db: Option<&'a Array2<f64>>,
+    /// Wall thickness map in mm
+    pub thickness: Option<&'a Array2<f64>>,
+    /// Amplitude of the back-wall gate in dB
+    pub backwall_db: Option<&'a Array2<f64>>,
+    /// Distance between two columns in mm
+    pub res_x: f64,
+    /// Distance between two rows in mm
+    pub res_y: f64
+}
+
+impl AcceptanceProfile {
+    /// Checks if the profile is valid and fits to the gates and A-Scans of a channel
+    ///
+    /// # Arguments
+    /// * `gates`: Gate configuration of the channel
+    /// * `samples`: Number of samples per A-Scan
+    ///
+    /// # Errors
+    /// A message is returned if the profile contains no rules, a limit is
+    /// negative or not finite, the aperture doesn't fit into the A-Scans
+    /// or a configuration is invalid
+    pub fn validate(&self, gates: &GateConfig, samples: usize) -> Result<(), String> {
+        if self.indications.is_none() && self.thickness.is_none() && self.backwall.is_none() {
+            return Err(String::from("The profile contains no rules!"));
+        }
+
+        self.db_reference.validate()?;
+
+        let mut limits = vec![];
+
+        if let Some(rules) = &self.indications {
+            rules.detection.validate()?;
+
+            rules.gate.validate(samples).map_err(|error| format!("Aperture of the indication rules: {}", error))?;
+
+            limits.extend(rules.max_size.iter().chain(rules.max_area.iter()).copied());
+            limits.extend(rules.zones.iter().map(|zone| zone.max_area));
+        }
+
+        if let Some(rule) = &self.thickness {
+            rule.config.validate(gates)?;
+            limits.push(rule.min);
+        }
+
+        if let Some(rule) = &self.backwall {
+            if gates.index_of(&rule.gate).is_none() {
+                return Err(format!("Unknown gate {}!", rule.gate));
+            }
+
+            if rule.reference.is_some_and(|reference| !reference.is_finite()) {
+                return Err(String::from("The back-wall reference has to be finite!"));
+            }
+
+            limits.push(rule.max_loss);
+        }
+
+        if limits.iter().any(|limit| !limit.is_finite() || *limit < 0.0) {
+            return Err(String::from("The limits of the profile have to be positive!"));
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the profile
+    ///
+    /// # Arguments
+    /// * `scans`: Scans required by the rules of the profile
+    ///
+    /// # Returns
+    /// The verdict with all violated rules
+    ///
+    /// # Errors
+    /// A message is returned if a zone is invalid or a required scan is missing
+    pub fn evaluate(&self, scans: &AcceptanceScans) -> Result<AcceptanceResult, String> {
+        let mut violations = vec![];
+        let mut threshold = None;
+        let mut indication_count = 0;
+
+        if let Some(rules) = &self.indications {
+            let c_scan_db = scans.c_scan_db.ok_or("The C-Scan is missing!")?;
+            let level = rules.detection.threshold_db(c_scan_db);
+            let min_area = rules.detection.min_area.unwrap_or(0.0);
+            let indications = defect::detect(c_scan_db, level, scans.res_x, scans.res_y, min_area);
+
+            if let Some(limit) = rules.max_size {
+                violations.extend(indication_violation(AcceptanceRule::IndicationSize, limit, &indications,
+                    |indication| indication.length_6db.max(indication.width_6db)));
+            }
+
+            if let Some(limit) = rules.max_area {
+                violations.extend(indication_violation(AcceptanceRule::IndicationArea, limit, &indications, |indication| indication.area));
+            }
+
+            let (rows, cols) = c_scan_db.dim();
+
+            for zone in &rules.zones {
+                let mask = zone.roi.clone().resolve(rows, cols, scans.res_x, scans.res_y)
+                    .map_err(|error| format!("Zone {}: {}", zone.name, error))?.mask;
+                let zone_scan = Array2::from_shape_fn((rows, cols), |index| if mask[index] { c_scan_db[index] } else { f64::NAN });
+                let zone_indications = defect::detect(&zone_scan, level, scans.res_x, scans.res_y, min_area);
+                let area: f64 = zone_indications.iter().map(|indication| indication.area).sum();
+
+                if area > zone.max_area {
+                    violations.push(Violation {
+                        rule: AcceptanceRule::ZoneArea, zone: Some(zone.name.clone()), limit: zone.max_area, value: area, area: None,
+                        missing_area: None, indications: zone_indications
+                    });
+                }
+            }
+
+            threshold = Some(level);
+            indication_count = indications.len();
+        }
+
+        if let Some(rule) = &self.thickness {
+            let thickness = scans.thickness.ok_or("The thickness map is missing!")?;
+            violations.extend(map_violation(AcceptanceRule::MinThickness, rule.min, thickness.iter().copied(),
+                |value| value < rule.min, f64::min, scans));
+        }
+
+        if let Some(rule) = &self.backwall {
+            let backwall_db = scans.backwall_db.ok_or("The back-wall amplitude is missing!")?;
+            let reference = rule.reference
+                .unwrap_or_else(|| backwall_db.iter().copied().filter(|value| value.is_finite()).reduce(f64::max).unwrap_or(f64::NAN));
+            violations.extend(map_violation(AcceptanceRule::BackwallLoss, rule.max_loss, backwall_db.iter().map(|value| reference - value),
+                |loss| loss > rule.max_loss, f64::max, scans));
+        }
+
+        Ok(AcceptanceResult { profile: self.name.clone(), passed: violations.is_empty(), threshold, indication_count, violations })
+    }
+}
+
+/// Checks a limit of the individual indications
+///
+/// # Arguments
+/// * `rule`: Checked rule
+/// * `limit`: Maximum allowed value
+/// * `indications`: Detected indications
+/// * `measure`: Measured value of an indication
+///
+/// # Returns
+/// The violation with all indications exceeding the limit or **None** if there are none
+fn indication_violation(rule: AcceptanceRule, limit: f64, indications: &[Indication], measure: fn(&Indication) -> f64) -> Option<Violation> {
+    let violating: Vec<Indication> = indications.iter().filter(|indication| measure(indication) > limit).cloned().collect();
+    let value = violating.iter().map(measure).reduce(f64::max)?;
+
+    Some(Violation { rule, zone: None, limit, value, area: None, missing_area: None, indications: violating })
+}
+
+/// Checks a limit of each datapoint of a map
+///
+/// Datapoints without a measurement (`NaN`) violate the limit, since the
+/// echo the rule relies on hasn't been detected.
+///
+/// # Arguments
+/// * `rule`: Checked rule
+/// * `limit`: Limit of the rule
+/// * `values`: Measured values of the datapoints
+/// * `violates`: Checks if a measured value violates the limit
+/// * `worst`: Selects the worse of two values
+/// * `scans`: Scans providing the resolution
+///
+/// # Returns
+/// The violation with the area of all violating datapoints or **None** if there are none
+fn map_violation(rule: AcceptanceRule, limit: f64, values: impl Iterator<Item = f64>, violates: impl Fn(f64) -> bool,
+    worst: fn(f64, f64) -> f64, scans: &AcceptanceScans) -> Option<Violation> {
+    let (mut count, mut missing, mut value) = (0, 0, None);
+
+    for measured in values {
+        if measured.is_nan() {
+            missing += 1;
+        }
+        else if violates(measured) {
+            count += 1;
+            value = Some(value.map_or(measured, |value| worst(value, measured)));
+        }
+    }
+
+    if count + missing == 0 {
+        return None;
+    }
+
+    let area = scans.res_x * scans.res_y;
+
+    Some(Violation {
+        rule, zone: None, limit, value: value.unwrap_or(f64::NAN),
+        area: Some((count + missing) as f64 * area), missing_area: Some(missing as f64 * area), indications: vec![]
+    })
+}
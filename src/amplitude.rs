@@ -0,0 +1,121 @@
+use ndarray::{Array, ArrayBase, Data, Dimension};
+use rocket::FromFormField;
+use serde::{Serialize, Deserialize};
+
+use crate::signal::AmplitudeMode;
+
+/// Number of counts between the normalized values `0` and `1`
+const HALF_RANGE: f64 = (i16::MAX as f64 - i16::MIN as f64) / 2.0;
+
+/// Conversion between normalized samples and counts of the 16 bit digitizer,
+/// which maps `i16::MIN` to `-1` and `i16::MAX` to `1`
+pub const COUNTS: UnitScale = UnitScale { factor: HALF_RANGE, offset: HALF_RANGE + i16::MIN as f64 };
+
+/// Unit of amplitudes and samples
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum AmplitudeUnit {
+    /// Samples normalized to `[-1, 1]`
+    #[default]
+    #[field(value = "normalized")]
+    Normalized,
+    /// Percent of full screen height
+    #[field(value = "fsh")]
+    Fsh,
+    /// Counts of the 16 bit digitizer
+    #[field(value = "counts")]
+    Counts,
+    /// Volts, requires the voltage range of the channel
+    #[field(value = "volts")]
+    Volts
+}
+
+/// Conversion of normalized values into a unit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnitScale {
+    /// Value of full screen height in the unit
+    factor: f64,
+    /// Offset of the samples in the unit
+    offset: f64
+}
+
+impl AmplitudeUnit {
+    /// Creates the conversion into this unit
+    ///
+    /// # Arguments
+    /// * `voltage_range`: Voltage of full screen height of the channel, if it has been set
+    ///
+    /// # Errors
+    /// A message is returned if volts are requested without a voltage range
+    pub fn scale(self, voltage_range: Option<f64>) -> Result<UnitScale, String> {
+        match self {
+            AmplitudeUnit::Normalized => Ok(UnitScale { factor: 1.0, offset: 0.0 }),
+            AmplitudeUnit::Fsh => Ok(UnitScale { factor: 100.0, offset: 0.0 }),
+            AmplitudeUnit::Counts => Ok(COUNTS),
+            AmplitudeUnit::Volts => voltage_range.map(|range| UnitScale { factor: range, offset: 0.0 })
+                .ok_or(String::from("No voltage range has been set for this channel!"))
+        }
+    }
+
+    /// Returns the name of the unit as used in requests
+    pub fn name(self) -> &'static str {
+        match self {
+            AmplitudeUnit::Normalized => "normalized",
+            AmplitudeUnit::Fsh => "fsh",
+            AmplitudeUnit::Counts => "counts",
+            AmplitudeUnit::Volts => "volts"
+        }
+    }
+
+    /// Returns the symbol of the unit
+    pub fn symbol(self) -> &'static str {
+        match self {
+            AmplitudeUnit::Normalized => "normalized",
+            AmplitudeUnit::Fsh => "%FSH",
+            AmplitudeUnit::Counts => "counts",
+            AmplitudeUnit::Volts => "V"
+        }
+    }
+}
+
+impl UnitScale {
+    /// Converts a normalized sample
+    ///
+    /// # Arguments
+    /// * `value`: Normalized sample
+    pub fn sample(&self, value: f64) -> f64 {
+        value * self.factor + self.offset
+    }
+
+    /// Converts a sample of the unit into a normalized sample, the inverse of `sample`
+    ///
+    /// # Arguments
+    /// * `value`: Sample in the unit
+    pub fn normalize(&self, value: f64) -> f64 {
+        (value - self.offset) / self.factor
+    }
+
+    /// Converts a value of an amplitude measure
+    ///
+    /// # Arguments
+    /// * `value`: Value of the measure based on normalized samples
+    /// * `mode`: Amplitude measure, power measures are scaled with the squared factor
+    pub fn amplitude(&self, value: f64, mode: AmplitudeMode) -> f64 {
+        if mode.is_power() {
+            value * self.factor * self.factor
+        }
+        else {
+            value * self.factor
+        }
+    }
+
+    /// Converts a scan of an amplitude measure
+    ///
+    /// # Arguments
+    /// * `scan`: Values of the measure based on normalized samples
+    /// * `mode`: Amplitude measure of the scan
+    pub fn convert<S, D>(&self, scan: &ArrayBase<S, D>, mode: AmplitudeMode) -> Array<f64, D>
+        where S: Data<Elem = f64>, D: Dimension {
+        scan.mapv(|value| self.amplitude(value, mode))
+    }
+}
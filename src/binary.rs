@@ -0,0 +1,120 @@
+use std::{convert::Infallible, io::Cursor};
+use rocket::{Request, Response, http::{Accept, ContentType, MediaType, Status, uncased::UncasedStr}, request::{FromRequest, Outcome}, response::{self, Responder}};
+use serde::Serialize;
+
+/// Header describing the content of a `BinaryArray`
+#[derive(Serialize)]
+struct BinaryHeader<'a, T: Serialize> {
+    /// Shape of the array, the last axis is the fastest varying one
+    shape: &'a [usize],
+    /// Data type of the values
+    dtype: &'static str,
+    /// Additional information about the array, e.g. axis scaling
+    info: &'a T
+}
+
+/// Compact binary response for large arrays
+///
+/// The body consists of the length of the JSON header as little-endian `u32`,
+/// the JSON header containing `shape`, `dtype` and `info` and the values as
+/// little-endian `f32` in row-major order.
+pub struct BinaryArray<T: Serialize> {
+    /// Shape of the array
+    pub shape: Vec<usize>,
+    /// Values in row-major order
+    pub data: Vec<f32>,
+    /// Additional information about the array
+    pub info: T
+}
+
+impl<T: Serialize> BinaryArray<T> {
+    /// Serializes header and data into the binary representation
+    ///
+    /// # Returns
+    /// The body of the response or **None** if the header can't be serialized
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let header = serde_json::to_vec(&BinaryHeader { shape: &self.shape, dtype: "f32", info: &self.info }).ok()?;
+
+        let mut body = Vec::with_capacity(4 + header.len() + 4 * self.data.len());
+        body.extend((header.len() as u32).to_le_bytes());
+        body.extend(header);
+
+        for value in &self.data {
+            body.extend(value.to_le_bytes());
+        }
+
+        Some(body)
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for BinaryArray<T> {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let body = self.to_bytes().ok_or(Status::InternalServerError)?;
+
+        Response::build()
+            .header(ContentType::Binary)
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+/// Response format requested by the client
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// JSON representation (default)
+    Json,
+    /// `BinaryArray` representation, requested with `Accept: application/octet-stream`
+    Binary
+}
+
+impl ResponseFormat {
+    /// Selects the format from the `Accept` header of a request
+    ///
+    /// # Arguments
+    /// * `accept`: Accepted media types of the request
+    ///
+    /// # Returns
+    /// `Binary` if `application/octet-stream` has a higher quality than JSON or the
+    /// same quality and is listed first, else `Json`. Wildcards only match JSON.
+    pub fn from_accept(accept: Option<&Accept>) -> ResponseFormat {
+        let Some(accept) = accept else {
+            return ResponseFormat::Json;
+        };
+
+        match (quality(accept, &MediaType::Binary, false), quality(accept, &MediaType::JSON, true)) {
+            (Some((binary, _)), _) if binary <= 0.0 => ResponseFormat::Json,
+            (Some(_), None) => ResponseFormat::Binary,
+            (Some((binary, binary_index)), Some((json, json_index))) if binary > json || (binary == json && binary_index < json_index) => ResponseFormat::Binary,
+            _ => ResponseFormat::Json
+        }
+    }
+}
+
+/// Returns the quality of a media type in an `Accept` header
+///
+/// # Arguments
+/// * `accept`: Accepted media types
+/// * `media_type`: Requested media type
+/// * `wildcards`: Media ranges like `application/*` or `*/*` match the media type
+///
+/// # Returns
+/// The quality and position of the most specific matching media range or
+/// **None** if the media type isn't listed
+fn quality(accept: &Accept, media_type: &MediaType, wildcards: bool) -> Option<(f32, usize)> {
+    let matches = |range: &UncasedStr, value: &UncasedStr| range == value || (wildcards && range == "*");
+    let specificity = |range: &MediaType| (range.top() != "*") as u8 + (range.sub() != "*") as u8;
+
+    accept.iter().enumerate()
+        .filter(|(_, range)| matches(range.top(), media_type.top()) && matches(range.sub(), media_type.sub()))
+        .max_by_key(|(index, range)| (specificity(range.media_type()), usize::MAX - index))
+        .map(|(index, range)| (range.weight_or(1.0), index))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ResponseFormat {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ResponseFormat::from_accept(request.accept()))
+    }
+}
@@ -0,0 +1,145 @@
+use std::{any::Any, collections::{HashMap, VecDeque}, hash::{DefaultHasher, Hash, Hasher}, mem::size_of, sync::Arc};
+use ndarray::{ArrayBase, Dimension, OwnedRepr};
+use serde::Serialize;
+
+use crate::data::GateScan;
+
+/// Memory budget of the scan cache in bytes
+pub const MAX_CACHE_BYTES: usize = 512 * 1024 * 1024;
+
+/// Estimated memory footprint of a cached value
+pub trait CacheSize {
+    /// Returns the size of the value in bytes
+    fn cache_size(&self) -> usize;
+}
+
+impl<A, D: Dimension> CacheSize for ArrayBase<OwnedRepr<A>, D> {
+    fn cache_size(&self) -> usize {
+        self.len() * size_of::<A>()
+    }
+}
+
+impl CacheSize for Vec<GateScan> {
+    fn cache_size(&self) -> usize {
+        self.iter().map(|scan| scan.amplitude.cache_size() + scan.time.cache_size()).sum()
+    }
+}
+
+/// Cache for computed scans of the loaded dataset
+///
+/// Every key contains the identity of the dataset the scan has been computed from,
+/// so scans of a previously loaded dataset are never returned.
+/// The least recently inserted entries are dropped if the memory budget is exceeded.
+pub struct ScanCache {
+    /// Maximum total size of the cached scans in bytes
+    max_bytes: usize,
+    /// Total size of the cached scans in bytes
+    bytes: usize,
+    /// Identity of the dataset the scans are computed from
+    dataset: u64,
+    /// Cached scans and their size by their key
+    entries: HashMap<String, (Arc<dyn Any + Send + Sync>, usize)>,
+    /// Keys in order of insertion
+    order: VecDeque<String>
+}
+
+/// Computes the identity of a dataset
+///
+/// # Arguments
+/// * `data`: Binary content of the data file
+///
+/// # Returns
+/// A hash of the length and content of the file
+pub fn dataset_identity(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl ScanCache {
+    /// Creates an empty cache
+    ///
+    /// # Arguments
+    /// * `max_bytes`: Maximum total size of the cached scans in bytes
+    pub fn new(max_bytes: usize) -> ScanCache {
+        ScanCache { max_bytes, bytes: 0, dataset: 0, entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    /// Sets the dataset the following scans are computed from
+    ///
+    /// All scans are removed if the identity differs from the current dataset.
+    ///
+    /// # Arguments
+    /// * `identity`: Identity of the dataset, see [dataset_identity]
+    pub fn set_dataset(&mut self, identity: u64) {
+        if self.dataset != identity {
+            self.clear();
+            self.dataset = identity;
+        }
+    }
+
+    /// Generates the key of a scan
+    ///
+    /// # Arguments
+    /// * `kind`: Kind of the scan, e.g. `c_scan`
+    /// * `parameters`: All parameters the scan depends on
+    ///
+    /// # Returns
+    /// A key which is unique for the dataset, kind and parameters
+    pub fn key<P: Serialize>(&self, kind: &str, parameters: &P) -> String {
+        format!("{:016x}:{}:{}", self.dataset, kind, serde_json::to_string(parameters).unwrap())
+    }
+
+    /// Returns a cached scan
+    ///
+    /// # Arguments
+    /// * `key`: Key of the scan
+    ///
+    /// # Returns
+    /// The cached scan or **None** if it hasn't been cached or has a different type
+    pub fn get<T: Any + Send + Sync>(&self, key: &str) -> Option<Arc<T>> {
+        self.entries.get(key)?.0.clone().downcast::<T>().ok()
+    }
+
+    /// Adds a scan to the cache
+    ///
+    /// A scan exceeding the memory budget on its own isn't cached.
+    ///
+    /// # Arguments
+    /// * `key`: Key of the scan
+    /// * `value`: Computed scan
+    ///
+    /// # Returns
+    /// A shared reference to the scan
+    pub fn insert<T: Any + Send + Sync + CacheSize>(&mut self, key: String, value: T) -> Arc<T> {
+        let size = value.cache_size();
+        let value = Arc::new(value);
+
+        if size > self.max_bytes {
+            return value;
+        }
+
+        match self.entries.insert(key.clone(), (value.clone(), size)) {
+            Some((_, previous)) => self.bytes -= previous,
+            None => self.order.push_back(key)
+        }
+        self.bytes += size;
+
+        while self.bytes > self.max_bytes {
+            if let Some(oldest) = self.order.pop_front() {
+                if let Some((_, removed)) = self.entries.remove(&oldest) {
+                    self.bytes -= removed;
+                }
+            }
+        }
+
+        value
+    }
+
+    /// Removes all cached scans
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+}
@@ -0,0 +1,215 @@
+use ndarray::Array2;
+use serde::{Serialize, Deserialize};
+
+use crate::data::GateScan;
+use crate::gate::GateConfig;
+use crate::material::{Material, WaveMode};
+use crate::roi::Roi;
+
+/// Region of a known thickness
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationRegion {
+    /// Datapoints of the region
+    pub roi: Roi,
+    /// Known thickness in mm
+    pub thickness: f64,
+    /// Standard uncertainty of the thickness in mm (default: `0`)
+    #[serde(default)]
+    pub uncertainty: f64
+}
+
+/// Sound velocity calibration on regions of known thickness
+///
+/// A single region requires a reference gate, e.g. at the interface echo, so that the
+/// time of flight starts at zero thickness. Two or more regions (step heights) are
+/// fitted by a line, which eliminates a constant time offset like the start time of
+/// the A-Scans.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VelocityCalibration {
+    /// Name of the gate measuring the backwall echo
+    pub gate: String,
+    /// Optional name of the reference gate, see `ThicknessConfig`
+    pub reference: Option<String>,
+    /// Regions of known thickness
+    pub regions: Vec<CalibrationRegion>,
+    /// Wave mode of the measured echoes (default: `longitudinal`)
+    #[serde(default)]
+    pub wave: WaveMode,
+    /// Name of the library material the velocity should be saved to
+    pub save: Option<String>
+}
+
+/// Time of flight measured inside a region
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RegionMeasurement {
+    /// Known thickness in mm
+    pub thickness: f64,
+    /// Number of datapoints with a detected echo
+    pub count: usize,
+    /// Mean time of flight in µs
+    pub time: f64,
+    /// Sample standard deviation of the time of flight in µs
+    pub deviation: f64
+}
+
+/// Result of a velocity calibration
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CalibrationResult {
+    /// Estimated sound velocity in m/s
+    pub velocity: f64,
+    /// Standard uncertainty of the velocity in m/s
+    pub uncertainty: f64,
+    /// Time of flight at zero thickness in µs, only determined with several thicknesses
+    pub offset: Option<f64>,
+    /// Measurement of each region
+    pub regions: Vec<RegionMeasurement>,
+    /// Library entry the velocity has been saved to
+    pub material: Option<Material>
+}
+
+impl VelocityCalibration {
+    /// Checks if the calibration fits to the gates of a channel
+    ///
+    /// # Arguments
+    /// * `gates`: Gate configuration of the channel
+    ///
+    /// # Errors
+    /// A message is returned if a gate is unknown, no region is given, a single region has
+    /// no reference gate, a thickness isn't positive, an uncertainty is negative or several
+    /// regions have the same thickness only
+    pub fn validate(&self, gates: &GateConfig) -> Result<(), String> {
+        for name in std::iter::once(&self.gate).chain(self.reference.iter()) {
+            if gates.index_of(name).is_none() {
+                return Err(format!("Unknown gate {}!", name));
+            }
+        }
+
+        if self.regions.is_empty() {
+            return Err(String::from("At least one region of known thickness is required!"));
+        }
+
+        if self.regions.len() == 1 && self.reference.is_none() {
+            return Err(String::from("A single region requires a reference gate!"));
+        }
+
+        for region in &self.regions {
+            if !region.thickness.is_finite() || region.thickness <= 0.0 {
+                return Err(String::from("The thickness of a region has to be positive!"));
+            }
+
+            if !region.uncertainty.is_finite() || region.uncertainty < 0.0 {
+                return Err(String::from("The uncertainty of a thickness can't be negative!"));
+            }
+        }
+
+        if self.regions.len() > 1 && self.regions.iter().all(|region| region.thickness == self.regions[0].thickness) {
+            return Err(String::from("Step heights require regions of different thickness!"));
+        }
+
+        Ok(())
+    }
+
+    /// Calculates the time of flight of each datapoint
+    ///
+    /// # Arguments
+    /// * `gates`: Gate configuration of the channel
+    /// * `scans`: Scans of the gates in the order of `gates.gates`
+    ///
+    /// # Returns
+    /// A 2-D-Array containing the time of flight in µs, relative to the reference gate if set
+    pub fn time_of_flight(&self, gates: &GateConfig, scans: &[GateScan]) -> Array2<f64> {
+        let time = &scans[gates.index_of(&self.gate).unwrap()].time;
+
+        match &self.reference {
+            Some(reference) => time - &scans[gates.index_of(reference).unwrap()].time,
+            None => time.clone()
+        }
+    }
+
+    /// Estimates the sound velocity
+    ///
+    /// # Arguments
+    /// * `time_of_flight`: Time of flight of each datapoint in µs
+    /// * `masks`: Datapoints of each region in the order of `regions`
+    /// * `resolution`: Sample resolution in µs, its quantization error is added to the
+    ///   uncertainty of each mean time of flight
+    ///
+    /// # Returns
+    /// The velocity with its standard uncertainty, propagated from the scatter of the
+    /// times of flight, the sample resolution and the uncertainty of the thicknesses
+    ///
+    /// # Errors
+    /// A message is returned if no echo has been detected inside a region or the times
+    /// of flight don't increase with the thickness
+    pub fn estimate(&self, time_of_flight: &Array2<f64>, masks: &[Array2<bool>], resolution: f64) -> Result<CalibrationResult, String> {
+        let regions = self.regions.iter().zip(masks).enumerate().map(|(index, (region, mask))| {
+            let times: Vec<f64> = time_of_flight.iter().zip(mask.iter())
+                .filter(|(time, inside)| **inside && time.is_finite())
+                .map(|(time, _)| *time)
+                .collect();
+
+            if times.is_empty() {
+                return Err(format!("No echo detected inside region {}!", index + 1));
+            }
+
+            let count = times.len();
+            let time = times.iter().sum::<f64>() / count as f64;
+            let deviation = if count > 1 {
+                (times.iter().map(|value| (value - time).powi(2)).sum::<f64>() / (count - 1) as f64).sqrt()
+            }
+            else {
+                0.0
+            };
+
+            Ok(RegionMeasurement { thickness: region.thickness, count, time, deviation })
+        }).collect::<Result<Vec<RegionMeasurement>, String>>()?;
+
+        // squared standard uncertainty of each mean time of flight
+        let time_variance: Vec<f64> = regions.iter()
+            .map(|region| region.deviation.powi(2) / region.count as f64 + resolution.powi(2) / 12.0)
+            .collect();
+
+        let (slope, slope_uncertainty, offset) = if regions.len() == 1 {
+            let (region, uncertainty) = (&regions[0], self.regions[0].uncertainty);
+
+            if region.time <= 0.0 {
+                return Err(String::from("The time of flight has to be positive!"));
+            }
+
+            let slope = region.time / region.thickness;
+            let relative = (time_variance[0] / region.time.powi(2) + (uncertainty / region.thickness).powi(2)).sqrt();
+
+            (slope, slope * relative, None)
+        }
+        else {
+            let count = regions.len() as f64;
+            let mean_thickness = regions.iter().map(|region| region.thickness).sum::<f64>() / count;
+            let mean_time = regions.iter().map(|region| region.time).sum::<f64>() / count;
+            let sxx: f64 = regions.iter().map(|region| (region.thickness - mean_thickness).powi(2)).sum();
+            let sxy: f64 = regions.iter().map(|region| (region.thickness - mean_thickness) * (region.time - mean_time)).sum();
+            let slope = sxy / sxx;
+
+            if slope <= 0.0 {
+                return Err(String::from("The time of flight doesn't increase with the thickness!"));
+            }
+
+            // thickness uncertainties are converted into time uncertainties with the slope
+            let variance: f64 = regions.iter().zip(&self.regions).zip(&time_variance)
+                .map(|((region, known), variance)| (region.thickness - mean_thickness).powi(2) * (variance + (slope * known.uncertainty).powi(2)))
+                .sum::<f64>() / sxx.powi(2);
+
+            (slope, variance.sqrt(), Some(mean_time - slope * mean_thickness))
+        };
+
+        // pulse-echo: slope = 2 / velocity in µs/mm
+        let velocity = 2000.0 / slope;
+
+        Ok(CalibrationResult {
+            velocity,
+            uncertainty: velocity * slope_uncertainty / slope,
+            offset,
+            regions,
+            material: None
+        })
+    }
+}
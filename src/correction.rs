@@ -0,0 +1,109 @@
+use serde::{Serialize, Deserialize};
+
+use crate::data::SubSet;
+use crate::signal::DecibelReference;
+
+/// Kind of a distance amplitude correction curve
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveKind {
+    /// Time-corrected gain, the levels are added to the samples
+    Tcg,
+    /// Distance amplitude curve of reference reflectors, all amplitudes and
+    /// thresholds in dB are expressed relative to the curve
+    Dac
+}
+
+/// Point of a correction curve
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CurvePoint {
+    /// Time in µs
+    pub time: f64,
+    /// Gain in dB (TCG) or amplitude of the reference reflector in dB relative to
+    /// full screen height of the filtered A-Scan including the software gain (DAC)
+    pub level: f64
+}
+
+/// Distance amplitude correction of a channel
+///
+/// The curve is linearly interpolated between its points and kept constant
+/// before the first and after the last point. The correction is applied to the
+/// filtered samples before the evaluation, but not to the interface detection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CorrectionCurve {
+    /// Interpretation of the levels
+    pub kind: CurveKind,
+    /// Points of the curve with increasing time
+    pub points: Vec<CurvePoint>
+}
+
+impl CorrectionCurve {
+    /// Checks if the curve is valid
+    ///
+    /// # Errors
+    /// A message is returned if the curve has no points, a value isn't
+    /// finite or the times aren't strictly increasing
+    pub fn validate(&self) -> Result<(), String> {
+        if self.points.is_empty() {
+            return Err(String::from("The curve needs at least one point!"));
+        }
+
+        if self.points.iter().any(|point| !point.time.is_finite() || !point.level.is_finite()) {
+            return Err(String::from("The points of the curve have to be finite!"));
+        }
+
+        if self.points.windows(2).any(|points| points[0].time >= points[1].time) {
+            return Err(String::from("The times of the curve have to be strictly increasing!"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the gain applied at a time
+    ///
+    /// # Arguments
+    /// * `time`: Time in µs
+    ///
+    /// # Returns
+    /// The gain in dB, which is the negative level for DAC curves
+    pub fn gain(&self, time: f64) -> f64 {
+        let index = self.points.partition_point(|point| point.time <= time);
+
+        let level = match (self.points.get(index.wrapping_sub(1)), self.points.get(index)) {
+            (Some(before), Some(after)) => before.level + (after.level - before.level) * (time - before.time) / (after.time - before.time),
+            (Some(point), None) | (None, Some(point)) => point.level,
+            (None, None) => 0.0
+        };
+
+        match self.kind {
+            CurveKind::Tcg => level,
+            CurveKind::Dac => -level
+        }
+    }
+
+    /// Calculates the linear factor of each sample of an A-Scan
+    ///
+    /// # Arguments
+    /// * `subset`: Subset settings of the channel
+    /// * `samples`: Number of samples per A-Scan
+    pub fn factors(&self, subset: &SubSet, samples: usize) -> Vec<f64> {
+        (0..samples).map(|index| f64::powf(10.0, self.gain(subset.sample_time(index as f64)) / 20.0)).collect()
+    }
+
+    /// Returns the reference of the dB values of the corrected samples
+    ///
+    /// A DAC curve scales the reference reflectors to full screen height, so that
+    /// full screen height is the curve.
+    ///
+    /// # Arguments
+    /// * `requested`: Requested reference
+    ///
+    /// # Returns
+    /// `Fsh` for DAC curves, else the requested reference
+    pub fn decibel_reference(&self, requested: DecibelReference) -> DecibelReference {
+        match self.kind {
+            CurveKind::Tcg => requested,
+            CurveKind::Dac => DecibelReference::Fsh
+        }
+    }
+}
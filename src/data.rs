@@ -1,14 +1,21 @@
 use std::fs::File;
 use std::vec;
 use regex::Regex;
-use ndarray::{Array, ArrayBase, OwnedRepr, Dim, s};
+use ndarray::{Array, Array2, ArrayBase, ArrayView1, ArrayView3, OwnedRepr, Dim, Axis, s};
+use rayon::prelude::*;
 use serde::{Serialize, Deserialize};
-use iir_filters::sos::zpk2sos;
+use iir_filters::sos::{zpk2sos, Sos};
 use iir_filters::filter::{DirectForm2Transposed, Filter};
 use iir_filters::filter_design::{butter, FilterType};
 
+use crate::amplitude;
+use crate::gate::{Gate, NamedGate, TimeSlices};
+use crate::correction::{CorrectionCurve, CurveKind};
+use crate::signal::{AmplitudeMode, DecibelReference, Threshold, TofMethod};
+use crate::volume::FilteredVolume;
+
 /// Configuration description for a Butterworth Bandpass filter
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct FilterConfig {
     order: u32,
     min_freq: f64,
@@ -16,6 +23,30 @@ struct FilterConfig {
     apply: bool
 }
 
+/// Butterworth Bandpass filter which is designed once and applied to many A-Scans
+#[derive(Clone)]
+pub struct AScanFilter {
+    /// Configuration the filter has been designed from
+    config: FilterConfig,
+    /// Second order sections of the filter, **None** if filtering is disabled
+    sos: Option<Sos>,
+    /// Distance amplitude correction applied after filtering
+    correction: Option<Correction>,
+    /// Software gain in dB applied after filtering
+    gain: f64
+}
+
+/// Distance amplitude correction of the samples of a channel
+#[derive(Clone)]
+struct Correction {
+    /// Key identifying the correction curve
+    key: String,
+    /// The curve is a DAC curve
+    dac: bool,
+    /// Linear factor of each sample
+    factors: Vec<f64>
+}
+
 /// The header of a loaded dataset
 #[derive(Default, Serialize, Clone)]
 pub struct Header {
@@ -58,6 +89,69 @@ pub struct SubSet {
     pub gain: f64
 }
 
+/// C- and D-Scan of a single gate
+pub struct GateScan {
+    /// Amplitude of each datapoint
+    pub amplitude: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    /// Time of flight of each datapoint in µs
+    pub time: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>
+}
+
+/// Source of the filtered samples a scan is computed from
+#[derive(Clone, Copy)]
+pub enum Samples<'a> {
+    /// Raw samples of the channel which are filtered on demand
+    Raw(&'a AScanFilter),
+    /// Precomputed filtered volume of the channel
+    Filtered(&'a FilteredVolume)
+}
+
+impl<'a> Samples<'a> {
+    /// Selects the precomputed volume if available, else the raw samples
+    /// 
+    /// # Arguments
+    /// * `filter`: Current filter
+    /// * `volume`: Precomputed volume computed with the current filter
+    pub fn of(filter: &'a AScanFilter, volume: Option<&'a FilteredVolume>) -> Samples<'a> {
+        match volume {
+            Some(volume) => Samples::Filtered(volume),
+            None => Samples::Raw(filter)
+        }
+    }
+
+    /// Returns a key identifying the source, e.g. for caching
+    pub fn key(&self) -> String {
+        match self {
+            Samples::Raw(filter) => filter.key(),
+            Samples::Filtered(volume) => format!("{}:volume", volume.filter_key)
+        }
+    }
+
+    /// Returns the filter the samples are processed with
+    pub fn filter(&self) -> &AScanFilter {
+        match self {
+            Samples::Raw(filter) => filter,
+            Samples::Filtered(volume) => &volume.filter
+        }
+    }
+
+    /// Writes the filtered and corrected samples of a complete A-Scan into a buffer
+    /// 
+    /// # Arguments
+    /// * `a_scan`: Raw samples of the A-Scan
+    /// * `position`: (row, column) index of the A-Scan
+    /// * `output`: Buffer which is overwritten by the processed samples
+    pub fn trace(&self, a_scan: ArrayView1<f64>, position: (usize, usize), output: &mut Vec<f64>) {
+        match self {
+            Samples::Raw(filter) => filter.apply_to(a_scan, output),
+            Samples::Filtered(volume) => {
+                output.clear();
+                output.extend(volume.filtered.slice(s![position.0, position.1, ..]).iter().map(|sample| *sample as f64));
+            }
+        }
+    }
+}
+
 /// Structure for loaded ultrasonic data
 #[derive(Default)]
 pub struct UsData {
@@ -90,7 +184,7 @@ impl UsData {
     /// If the channel has been recorded the array storing its
     /// values will be returned, else **None**
     pub fn get_channel(&self, channel: usize) -> Option<&ArrayBase<OwnedRepr<f64>, Dim<[usize; 3]>>> {
-        if &self.datasets.len() > &channel {
+        if self.datasets.len() > channel {
             Some(&self.datasets[channel])
         }
         else {
@@ -98,6 +192,11 @@ impl UsData {
         }
     }
 
+    /// Returns the number of recorded channels
+    pub fn channel_count(&self) -> usize {
+        self.datasets.len()
+    }
+
     /// Get the subset settings for a specific channel
     /// 
     /// # Arguments
@@ -124,86 +223,292 @@ impl UsData {
     /// Generates the C-Scan of a specific channel
     /// # Arguments
     /// * `channel`: Channel number
-    /// * `start`: Start index for the aperture
-    /// * `end`: End index for the aperture
-    /// * `as_decibel`: Maximum should be returned as dB value
+    /// * `gate`: Gate defining the aperture
+    /// * `samples`: Source of the filtered samples
+    /// * `mode`: Amplitude measure evaluated inside the aperture
+    /// * `mask`: Optional mask of the datapoints which should be evaluated
     /// 
     /// # Returns
-    /// If the channel has been recorded a 2-D array containing the maximum of
-    /// each data point will be returned, else **None**
-    pub fn c_scan(&self, channel: usize, start: usize, end: usize, as_decibel: bool) -> Option<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>> {
-        let data = self.get_channel(channel);
-        let gain = self.get_channel_subset(channel).unwrap().gain;
-
-        match data {
-            Some(array) => {
-                let shape = array.shape();
-
-                let mut scan: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> = Array::zeros((shape[0], shape[1]));
-
-                for (row_index, row) in array.outer_iter().enumerate() {
-                    for (col_index, col) in row.outer_iter().enumerate() {
-                        let window = col.slice(s![start..end]);
-                        let filtered_window = filter_a_scan(&window.to_vec()).unwrap();
-
-                        let mut maximum: f64 = filtered_window.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-
-                        if as_decibel {
-                            maximum = 20.0 * (maximum * (f64::powi(2.0, 15) - 1.0)).abs().log10() - gain;
-                        }
-                        
-                        scan[[row_index, col_index]] = maximum;
-                    }
-                }
+    /// If the channel has been recorded a 2-D array containing the amplitude
+    /// measure of each data point will be returned, else **None**.
+    /// Datapoints where the gate can't be placed or outside of the mask contain `NaN`.
+    pub fn c_scan(&self, channel: usize, gate: &Gate, samples: Samples, mode: AmplitudeMode, mask: Option<&Array2<bool>>) -> Option<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>> {
+        let gain = self.checked_subset(channel)?.gain;
 
-                Some(scan)
-            }
+        Some(c_scan_of(self.datasets[channel].view(), samples, gate, gain, mode, mask))
+    }
 
-            None => {
-                println!("Invalid channel request");
-                None
+    /// Generates the D-Scan of a requested channel
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel number
+    /// * `gate`: Gate defining the aperture
+    /// * `samples`: Source of the filtered samples
+    /// * `method`: Method for detecting the time of flight
+    /// * `threshold`: Threshold used by the threshold based methods
+    /// * `mask`: Optional mask of the datapoints which should be evaluated
+    /// 
+    /// # Returns
+    /// If the channel has been recorded a 2-D array containing the time of
+    /// flight inside the aperture of each datapoint will be returned, else **None**.
+    /// Datapoints without a detected echo or gate or outside of the mask contain `NaN`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn d_scan(&self, channel: usize, gate: &Gate, samples: Samples, method: TofMethod, threshold: Threshold, mask: Option<&Array2<bool>>) -> Option<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>> {
+        let subset = self.checked_subset(channel)?;
+
+        Some(d_scan_of(self.datasets[channel].view(), samples, gate, subset, method, threshold, mask))
+    }
+
+    /// Generates the C- and D-Scans of several gates in a single pass
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel number
+    /// * `gates`: Gates with their evaluation settings
+    /// * `samples`: Source of the filtered samples
+    /// * `mask`: Optional mask of the datapoints which should be evaluated
+    /// 
+    /// # Returns
+    /// If the channel has been recorded a `GateScan` for each gate
+    /// will be returned in the order of `gates`, else **None**.
+    /// Datapoints outside of the mask contain `NaN`.
+    pub fn gate_scans(&self, channel: usize, gates: &[NamedGate], samples: Samples, mask: Option<&Array2<bool>>) -> Option<Vec<GateScan>> {
+        let subset = self.checked_subset(channel)?;
+
+        Some(gate_scans_of(self.datasets[channel].view(), samples, gates, subset, mask))
+    }
+
+    /// Generates C-Scans of consecutive time windows in a single pass
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel number
+    /// * `slices`: Validated time windows
+    /// * `samples`: Source of the filtered samples
+    /// * `decibel`: Reference if the amplitudes should be returned as dB values.
+    ///   The `scan_max` reference uses the maximum of the complete stack.
+    /// * `mode`: Amplitude measure evaluated inside each window
+    /// 
+    /// # Returns
+    /// If the channel has been recorded a 3-D array of shape `[windows, rows, columns]`
+    /// will be returned, else **None**. Each A-Scan is filtered completely before
+    /// it is split into the windows.
+    pub fn c_scan_stack(&self, channel: usize, slices: &TimeSlices, samples: Samples, decibel: Option<DecibelReference>, mode: AmplitudeMode) -> Option<ArrayBase<OwnedRepr<f32>, Dim<[usize; 3]>>> {
+        let gain = self.checked_subset(channel)?.gain;
+
+        let stack = c_scan_stack_of(self.datasets[channel].view(), samples, slices, mode);
+
+        match decibel {
+            Some(reference) => {
+                let scale = reference.scale(mode, gain, || stack.iter().fold(f64::NAN, |max, value| max.max(value.abs() as f64)));
+                Some(stack.mapv(|value| scale.apply(value as f64) as f32))
             }
+            None => Some(stack)
         }
     }
 
-    /// Generates the D-Scan of a requested channel
+    /// Returns the subset of a recorded channel
     /// 
     /// # Arguments
     /// * `channel`: Channel number
-    /// * `start`: Start index of the aperture
-    /// * `end`: End index of the aperture
     /// 
     /// # Returns
-    /// If the channel has been recorded a 2-D array containing the Argmax
-    /// inside the aperture of each datapoint will be returned, else **None**
-    pub fn d_scan(&self, channel: usize, start: usize, end: usize) -> Option<ArrayBase<OwnedRepr<u32>, Dim<[usize; 2]>>> {
-        let data_link = self.get_channel(channel);
+    /// The subset settings if the channel has been recorded, else **None**
+    fn checked_subset(&self, channel: usize) -> Option<&SubSet> {
+        match (self.get_channel(channel), self.get_channel_subset(channel)) {
+            (Some(_), Some(subset)) => Some(subset),
+            _ => {
+                println!("Invalid channel request");
+                None
+            }
+        }
+    }
+}
 
-        match data_link {
-            Some(data) => {
-                let shape = data.shape();
+/// Generates the C-Scan of the samples of a channel
+/// 
+/// # Arguments
+/// * `data`: Raw samples of the channel
+/// * `samples`: Source of the filtered samples
+/// * `gate`: Gate defining the aperture
+/// * `gain`: Gain of the channel
+/// * `mode`: Amplitude measure evaluated inside the aperture
+/// * `mask`: Optional mask of the datapoints which should be evaluated
+fn c_scan_of(data: ArrayView3<f64>, samples: Samples, gate: &Gate, gain: f64, mode: AmplitudeMode, mask: Option<&Array2<bool>>) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let shape = data.shape();
+
+    let mut scan: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> = Array::zeros((shape[0], shape[1]));
+
+    scan.axis_iter_mut(Axis(0)).into_par_iter().zip(data.axis_iter(Axis(0)).into_par_iter()).enumerate()
+        .for_each(|(row_index, (mut scan_row, row))| {
+            let (mut trace, mut scratch) = (vec![], vec![]);
+
+            for (col_index, (value, col)) in scan_row.iter_mut().zip(row.outer_iter()).enumerate() {
+                if !is_masked_in(mask, row_index, col_index) {
+                    *value = f64::NAN;
+                    continue;
+                }
 
-                let mut scan = Array::zeros((shape[0], shape[1]));
+                samples.trace(col, (row_index, col_index), &mut trace);
 
-                for (row_index, row) in data.outer_iter().enumerate() {
-                    for (col_index, col) in row.outer_iter().enumerate() {
-                        let window = col.slice(s![start..end]);
-                        let filtered_window = filter_a_scan(&window.to_vec()).unwrap();
+                *value = match gate.window(&trace, gain, samples.filter(), &mut scratch) {
+                    Some((start, end)) => mode.evaluate(&trace[start..end]),
+                    None => f64::NAN
+                };
+            }
+        });
 
-                        let maximum = filtered_window.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-                        let argmax = filtered_window.iter().position(|x| x == &maximum).unwrap();
+    scan
+}
 
-                        scan[[row_index, col_index]] = argmax as u32 + start as u32;
-                    }
+/// Generates the D-Scan of the samples of a channel
+/// 
+/// # Arguments
+/// * `data`: Raw samples of the channel
+/// * `samples`: Source of the filtered samples
+/// * `gate`: Gate defining the aperture
+/// * `subset`: Subset settings of the channel
+/// * `method`: Method for detecting the time of flight
+/// * `threshold`: Threshold used by the threshold based methods
+/// * `mask`: Optional mask of the datapoints which should be evaluated
+#[allow(clippy::too_many_arguments)]
+fn d_scan_of(data: ArrayView3<f64>, samples: Samples, gate: &Gate, subset: &SubSet, method: TofMethod, threshold: Threshold,
+    mask: Option<&Array2<bool>>) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let linear_threshold = threshold.linear(subset.gain, samples.filter().dac());
+    let shape = data.shape();
+
+    let mut scan = Array::zeros((shape[0], shape[1]));
+
+    scan.axis_iter_mut(Axis(0)).into_par_iter().zip(data.axis_iter(Axis(0)).into_par_iter()).enumerate()
+        .for_each(|(row_index, (mut scan_row, row))| {
+            let (mut trace, mut scratch) = (vec![], vec![]);
+
+            for (col_index, (value, col)) in scan_row.iter_mut().zip(row.outer_iter()).enumerate() {
+                if !is_masked_in(mask, row_index, col_index) {
+                    *value = f64::NAN;
+                    continue;
                 }
 
-                Some(scan)
+                samples.trace(col, (row_index, col_index), &mut trace);
+
+                *value = gate.window(&trace, subset.gain, samples.filter(), &mut scratch)
+                    .and_then(|(start, end)| method.detect(&trace[start..end], linear_threshold)
+                        .map(|position| subset.sample_time(position + start as f64)))
+                    .unwrap_or(f64::NAN);
             }
-            None => {
-                println!("Invalid channel request");
-                None
+        });
+
+    scan
+}
+
+/// Generates the C- and D-Scans of several gates of the samples of a channel
+/// 
+/// # Arguments
+/// * `data`: Raw samples of the channel
+/// * `samples`: Source of the filtered samples
+/// * `gates`: Gates with their evaluation settings
+/// * `subset`: Subset settings of the channel
+/// * `mask`: Optional mask of the datapoints which should be evaluated
+fn gate_scans_of(data: ArrayView3<f64>, samples: Samples, gates: &[NamedGate], subset: &SubSet, mask: Option<&Array2<bool>>) -> Vec<GateScan> {
+    let shape = data.shape();
+
+    let thresholds: Vec<f64> = gates.iter().map(|gate| gate.threshold.linear(subset.gain, samples.filter().dac())).collect();
+
+    // amplitude and time of each gate for each datapoint of a row
+    let rows: Vec<Vec<(f64, f64)>> = data.axis_iter(Axis(0)).into_par_iter().enumerate().map(|(row_index, row)| {
+        let (mut trace, mut scratch) = (vec![], vec![]);
+        let mut values = Vec::with_capacity(shape[1] * gates.len());
+
+        for (col_index, col) in row.outer_iter().enumerate() {
+            if !is_masked_in(mask, row_index, col_index) {
+                values.extend(gates.iter().map(|_| (f64::NAN, f64::NAN)));
+                continue;
+            }
+
+            samples.trace(col, (row_index, col_index), &mut trace);
+
+            for (gate, threshold) in gates.iter().zip(&thresholds) {
+                values.push(match gate.gate.window(&trace, subset.gain, samples.filter(), &mut scratch) {
+                    Some((start, end)) => {
+                        let window = &trace[start..end];
+                        let time = gate.method.detect(window, *threshold)
+                            .map(|position| subset.sample_time(position + start as f64))
+                            .unwrap_or(f64::NAN);
+
+                        (gate.mode.evaluate(window), time)
+                    }
+                    None => (f64::NAN, f64::NAN)
+                });
             }
         }
+
+        values
+    }).collect();
+
+    let mut scans: Vec<GateScan> = gates.iter().map(|_| GateScan {
+        amplitude: Array::zeros((shape[0], shape[1])),
+        time: Array::zeros((shape[0], shape[1]))
+    }).collect();
+
+    for (row_index, values) in rows.iter().enumerate() {
+        for (index, (amplitude, time)) in values.iter().enumerate() {
+            let (col_index, gate_index) = (index / gates.len(), index % gates.len());
+
+            scans[gate_index].amplitude[[row_index, col_index]] = *amplitude;
+            scans[gate_index].time[[row_index, col_index]] = *time;
+        }
+    }
+
+    scans
+}
+
+/// Checks if a datapoint should be evaluated
+/// 
+/// # Arguments
+/// * `mask`: Optional mask of the datapoints which should be evaluated
+/// * `row`: Row index
+/// * `col`: Column index
+fn is_masked_in(mask: Option<&Array2<bool>>, row: usize, col: usize) -> bool {
+    mask.is_none_or(|mask| mask[[row, col]])
+}
+
+/// Generates C-Scans of consecutive time windows of the samples of a channel
+/// 
+/// # Arguments
+/// * `data`: Raw samples of the channel
+/// * `samples`: Source of the filtered samples
+/// * `slices`: Validated time windows
+/// * `mode`: Amplitude measure evaluated inside each window
+fn c_scan_stack_of(data: ArrayView3<f64>, samples: Samples, slices: &TimeSlices, mode: AmplitudeMode) -> ArrayBase<OwnedRepr<f32>, Dim<[usize; 3]>> {
+    let windows = slices.windows();
+    let shape = data.shape();
+
+    let mut stack = Array::zeros((windows.len(), shape[0], shape[1]));
+
+    stack.axis_iter_mut(Axis(1)).into_par_iter().zip(data.axis_iter(Axis(0)).into_par_iter()).enumerate()
+        .for_each(|(row_index, (mut stack_row, row))| {
+            let mut filtered = vec![];
+
+            for (col_index, col) in row.outer_iter().enumerate() {
+                samples.trace(col, (row_index, col_index), &mut filtered);
+
+                for (window_index, (start, end)) in windows.iter().enumerate() {
+                    stack_row[[window_index, col_index]] = mode.evaluate(&filtered[*start..*end]) as f32;
+                }
+            }
+        });
+
+    stack
+}
+
+impl SubSet {
+    /// Converts a (fractional) sample index into the time of the A-Scan axis
+    /// 
+    /// # Arguments
+    /// * `position`: Sample index
+    /// 
+    /// # Returns
+    /// The time in µs
+    pub fn sample_time(&self, position: f64) -> f64 {
+        self.min_sample_pos as f64 + position * self.sample_resolution as f64 / 1000.0
     }
 }
 
@@ -249,7 +554,7 @@ fn parse_sonoware_file(binary_data: Vec<u8>) -> Option<UsData> {
                 data_bytes.drain(0..values as usize);
 
                 if subset.name.contains("Data") {
-                    let sub_data = get_raw_data(&sub_sample, &subset, *samples_x, *samples_y);
+                    let sub_data = get_raw_data(&sub_sample, subset, *samples_x, *samples_y);
 
                     us_data.datasets.push(sub_data);
                 }
@@ -268,6 +573,7 @@ fn parse_sonoware_file(binary_data: Vec<u8>) -> Option<UsData> {
 /// 
 /// # Arguments
 /// * `header`: String representation of the header
+/// * `gains`: Gain of each subset
 /// 
 /// # Returns
 /// A `Header` struct containing the data of the provided header
@@ -303,7 +609,7 @@ fn parse_header(header: String, gains: Vec<f64>) -> Header {
         }
     }
 
-    let channels = sub_sets.iter().filter(|&n| (*n).name.contains("Data")).count() as u8;
+    let channels = sub_sets.iter().filter(|&n| n.name.contains("Data")).count() as u8;
 
     Header { 
         format, 
@@ -392,13 +698,12 @@ fn get_float_entry(line: &str, evaluate_time: bool) -> Option<f32> {
 /// 
 /// # Returns
 /// A 3-D-Array of shape `[y, x, subset samples]` is returned containing the values
-/// as `i16`.
+/// normalized to `[-1, 1]` by `amplitude::COUNTS`.
 fn get_raw_data(data: &Vec<&u8>, sub_set: &SubSet, x: u16, y: u16) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 3]>> {    
     let mut array: ArrayBase<OwnedRepr<f64>, Dim<[usize; 3]>> = Array::zeros((y as usize, x as usize, sub_set.sample_nums as usize));
     
-    let mut i = 0;
-
-    for chunk in data.chunks(sub_set.element_size as usize) {
+    for (i, chunk) in data.chunks(sub_set.element_size as usize).enumerate() {
+        let i = i as u32;
         let mut bytes: [u8; 2] = [0, 0];
 
         bytes[0] = *chunk[0];
@@ -408,33 +713,148 @@ fn get_raw_data(data: &Vec<&u8>, sub_set: &SubSet, x: u16, y: u16) -> ArrayBase<
         let col = (i / sub_set.sample_nums) % x as u32;
         let row = i / (sub_set.sample_nums * x as u32);
 
-        let value = (i16::from_be_bytes(bytes) as f64 - i16::MIN as f64) / (i16::MAX as f64 - i16::MIN as f64) * 2.0 - 1.0;
-
-        array[[row as usize, col as usize, sample as usize]] = value;
-        i += 1;
+        array[[row as usize, col as usize, sample as usize]] = amplitude::COUNTS.normalize(i16::from_be_bytes(bytes) as f64);
     }
 
     array
 }
 
-pub fn filter_a_scan(a_scan: &Vec<f64>) -> Option<Vec<f64>> {
-    let mut output = vec![];
+impl AScanFilter {
+    /// Loads the filter configuration and designs the filter
+    /// 
+    /// # Returns
+    /// The filter described by `filter_config.json`
+    pub fn load() -> AScanFilter {
+        let config: FilterConfig = serde_json::from_reader(File::open("filter_config.json").unwrap()).unwrap();
 
-    let config: FilterConfig = serde_json::from_reader(File::open("filter_config.json").unwrap()).unwrap();
+        let sos = if config.apply {
+            let fs = 1e4;
+            let zpk = butter(config.order, FilterType::BandPass(config.min_freq, config.max_freq), fs).unwrap();
 
-    if config.apply {
-        let fs = 1e4;
-        let zpk = butter(config.order, FilterType::BandPass(config.min_freq, config.max_freq), fs).unwrap();
+            Some(zpk2sos(&zpk, None).unwrap())
+        }
+        else {
+            None
+        };
 
-        let sos = zpk2sos(&zpk, None).unwrap();
-        let mut filtering = DirectForm2Transposed::new(&sos);
+        AScanFilter { config, sos, correction: None, gain: 0.0 }
+    }
 
-        for sample in a_scan.iter() {
-            output.push(filtering.filter((*sample).into()));
+    /// Adds a distance amplitude correction to the filter
+    /// 
+    /// # Arguments
+    /// * `curve`: Validated correction curve of the channel
+    /// * `subset`: Subset settings of the channel
+    /// * `samples`: Number of samples per A-Scan
+    pub fn with_correction(self, curve: &CorrectionCurve, subset: &SubSet, samples: usize) -> AScanFilter {
+        AScanFilter {
+            correction: Some(Correction {
+                key: serde_json::to_string(curve).unwrap(),
+                dac: curve.kind == CurveKind::Dac,
+                factors: curve.factors(subset, samples)
+            }),
+            ..self
         }
-    
-        return Some(output);
     }
 
-    Some(a_scan.clone())
+    /// Adds a software gain to the filter
+    /// 
+    /// # Arguments
+    /// * `gain`: Gain in dB
+    pub fn with_gain(self, gain: f64) -> AScanFilter {
+        AScanFilter { gain, ..self }
+    }
+
+    /// Checks if the samples are corrected by a DAC curve, see `CorrectionCurve::decibel_reference`
+    pub fn dac(&self) -> bool {
+        self.correction.as_ref().is_some_and(|correction| correction.dac)
+    }
+
+    /// Returns a key identifying the filter configuration, correction and gain, e.g. for caching
+    pub fn key(&self) -> String {
+        let mut key = serde_json::to_string(&self.config).unwrap();
+
+        if let Some(correction) = &self.correction {
+            key = format!("{}:{}", key, correction.key);
+        }
+
+        if self.gain != 0.0 {
+            key = format!("{}:gain={}", key, self.gain);
+        }
+
+        key
+    }
+
+    /// Filters an A-Scan into an existing buffer
+    /// 
+    /// # Arguments
+    /// * `a_scan`: Samples to filter
+    /// * `output`: Buffer which is overwritten by the filtered samples
+    pub fn apply_into<A: Copy + Into<f64>>(&self, a_scan: ArrayView1<A>, output: &mut Vec<f64>) {
+        output.clear();
+
+        match &self.sos {
+            Some(sos) => {
+                let mut filtering = DirectForm2Transposed::new(sos);
+                output.extend(a_scan.iter().map(|sample| filtering.filter((*sample).into())));
+            }
+            None => {
+                output.extend(a_scan.iter().map(|sample| (*sample).into()));
+            }
+        }
+    }
+
+    /// Filters a complete A-Scan and applies the distance amplitude correction
+    /// and the software gain
+    /// 
+    /// The samples are rounded to single precision like the precomputed volumes,
+    /// so scans are identical whether they are computed from the raw samples or a volume.
+    /// 
+    /// # Arguments
+    /// * `a_scan`: Samples of the complete A-Scan
+    /// * `output`: Buffer which is overwritten by the corrected samples
+    pub fn apply_to<A: Copy + Into<f64>>(&self, a_scan: ArrayView1<A>, output: &mut Vec<f64>) {
+        self.apply_into(a_scan, output);
+
+        if let Some(correction) = &self.correction {
+            output.iter_mut().zip(&correction.factors).for_each(|(sample, factor)| *sample *= factor);
+        }
+
+        if self.gain != 0.0 {
+            let factor = f64::powf(10.0, self.gain / 20.0);
+            output.iter_mut().for_each(|sample| *sample *= factor);
+        }
+
+        output.iter_mut().for_each(|sample| *sample = *sample as f32 as f64);
+    }
+
+    /// Reverts the distance amplitude correction and the software gain of a section
+    /// of a corrected A-Scan
+    /// 
+    /// # Arguments
+    /// * `samples`: Corrected samples, overwritten by the filtered samples
+    /// * `offset`: Index of the first sample inside the complete A-Scan
+    pub fn remove_correction(&self, samples: &mut [f64], offset: usize) {
+        if let Some(correction) = &self.correction {
+            samples.iter_mut().zip(correction.factors.iter().skip(offset)).for_each(|(sample, factor)| *sample /= factor);
+        }
+
+        if self.gain != 0.0 {
+            let factor = f64::powf(10.0, self.gain / 20.0);
+            samples.iter_mut().for_each(|sample| *sample /= factor);
+        }
+    }
+
+    /// Filters an A-Scan and applies the distance amplitude correction and the software gain
+    /// 
+    /// # Arguments
+    /// * `a_scan`: Samples of the complete A-Scan
+    /// 
+    /// # Returns
+    /// The filtered samples
+    pub fn apply(&self, a_scan: ArrayView1<f64>) -> Vec<f64> {
+        let mut output = Vec::with_capacity(a_scan.len());
+        self.apply_to(a_scan, &mut output);
+        output
+    }
 }
@@ -0,0 +1,213 @@
+use ndarray::Array2;
+use rocket::{FromForm, FromFormField};
+use serde::{Serialize, Deserialize};
+
+/// Interpretation of the segmentation threshold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum DefectLevel {
+    /// The threshold is an absolute level in dB
+    #[default]
+    #[field(value = "absolute")]
+    Absolute,
+    /// The threshold is a drop in dB below the reference level
+    #[field(value = "drop")]
+    Drop
+}
+
+/// Configuration of the defect detection on a C-Scan in dB
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromForm)]
+pub struct DefectConfig {
+    /// Absolute level or drop below the reference level in dB
+    pub threshold: f64,
+    /// Interpretation of the threshold (default: `absolute`)
+    pub level: Option<DefectLevel>,
+    /// Reference level in dB for the `drop` level (default: maximum of the C-Scan)
+    pub reference: Option<f64>,
+    /// Minimum area of an indication in mm² (default: `0`)
+    pub min_area: Option<f64>
+}
+
+impl DefectConfig {
+    /// Checks if the configuration is valid
+    ///
+    /// # Errors
+    /// A message is returned if a value isn't finite, the drop is negative
+    /// or the minimum area is negative
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.threshold.is_finite() || self.reference.is_some_and(|reference| !reference.is_finite()) {
+            return Err(String::from("The defect threshold and reference have to be finite!"));
+        }
+
+        if self.level == Some(DefectLevel::Drop) && self.threshold < 0.0 {
+            return Err(String::from("The drop below the reference level can't be negative!"));
+        }
+
+        if self.min_area.is_some_and(|area| area.is_nan() || area < 0.0) {
+            return Err(String::from("The minimum area can't be negative!"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the absolute threshold in dB
+    ///
+    /// # Arguments
+    /// * `c_scan_db`: C-Scan in dB
+    ///
+    /// # Returns
+    /// The threshold or `NaN` if the reference is taken from a C-Scan without valid values
+    pub fn threshold_db(&self, c_scan_db: &Array2<f64>) -> f64 {
+        match self.level.unwrap_or_default() {
+            DefectLevel::Absolute => self.threshold,
+            DefectLevel::Drop => self.reference.unwrap_or_else(|| scan_max(c_scan_db)) - self.threshold
+        }
+    }
+}
+
+/// Connected region of a C-Scan exceeding the threshold
+///
+/// Positions are given in mm relative to the first datapoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Indication {
+    /// Number of the indication, starting at `1`
+    pub id: usize,
+    /// Number of datapoints of the indication
+    pub datapoints: usize,
+    /// Area in mm²
+    pub area: f64,
+    /// Leftmost datapoint
+    pub x_min: f64,
+    /// Rightmost datapoint
+    pub x_max: f64,
+    /// Uppermost datapoint
+    pub y_min: f64,
+    /// Lowermost datapoint
+    pub y_max: f64,
+    /// Horizontal position of the centroid
+    pub centroid_x: f64,
+    /// Vertical position of the centroid
+    pub centroid_y: f64,
+    /// Peak amplitude in dB
+    pub peak: f64,
+    /// Horizontal position of the peak amplitude
+    pub peak_x: f64,
+    /// Vertical position of the peak amplitude
+    pub peak_y: f64,
+    /// Horizontal extent of the datapoints within 6 dB of the peak
+    pub length_6db: f64,
+    /// Vertical extent of the datapoints within 6 dB of the peak
+    pub width_6db: f64,
+    /// Area of the datapoints within 6 dB of the peak in mm²
+    pub area_6db: f64
+}
+
+/// Detects the indications of a C-Scan
+///
+/// Datapoints reaching the threshold are combined with their eight neighbours
+/// into connected indications. Extents include the pitch of the datapoints,
+/// e.g. a single datapoint has the length `res_x`.
+///
+/// # Arguments
+/// * `c_scan_db`: C-Scan in dB, `NaN` datapoints are ignored
+/// * `threshold`: Minimum amplitude of an indication in dB
+/// * `res_x`: Distance between two columns in mm
+/// * `res_y`: Distance between two rows in mm
+/// * `min_area`: Minimum area of an indication in mm²
+///
+/// # Returns
+/// The indications sorted by their first datapoint in row-major order
+pub fn detect(c_scan_db: &Array2<f64>, threshold: f64, res_x: f64, res_y: f64, min_area: f64) -> Vec<Indication> {
+    let (rows, cols) = c_scan_db.dim();
+    let mut visited = Array2::from_elem((rows, cols), false);
+    let mut indications = vec![];
+
+    for ((row, col), value) in c_scan_db.indexed_iter() {
+        if visited[[row, col]] || value.is_nan() || *value < threshold {
+            continue;
+        }
+
+        let mut datapoints = vec![];
+        let mut stack = vec![(row, col)];
+        visited[[row, col]] = true;
+
+        while let Some((row, col)) = stack.pop() {
+            datapoints.push((row, col));
+
+            for neighbour_row in row.saturating_sub(1)..(row + 2).min(rows) {
+                for neighbour_col in col.saturating_sub(1)..(col + 2).min(cols) {
+                    if !visited[[neighbour_row, neighbour_col]] && c_scan_db[[neighbour_row, neighbour_col]] >= threshold {
+                        visited[[neighbour_row, neighbour_col]] = true;
+                        stack.push((neighbour_row, neighbour_col));
+                    }
+                }
+            }
+        }
+
+        if datapoints.len() as f64 * res_x * res_y >= min_area {
+            indications.push(size(c_scan_db, &datapoints, indications.len() + 1, res_x, res_y));
+        }
+    }
+
+    indications
+}
+
+/// Measures a connected indication
+fn size(c_scan_db: &Array2<f64>, datapoints: &[(usize, usize)], id: usize, res_x: f64, res_y: f64) -> Indication {
+    let &(peak_row, peak_col) = datapoints.iter()
+        .max_by(|a, b| c_scan_db[**a].total_cmp(&c_scan_db[**b])).unwrap();
+    let peak = c_scan_db[[peak_row, peak_col]];
+
+    let (rows, cols) = bounds(datapoints);
+    let within_6db: Vec<(usize, usize)> = datapoints.iter().copied().filter(|datapoint| c_scan_db[*datapoint] >= peak - 6.0).collect();
+    let (rows_6db, cols_6db) = bounds(&within_6db);
+
+    let count = datapoints.len() as f64;
+
+    Indication {
+        id,
+        datapoints: datapoints.len(),
+        area: count * res_x * res_y,
+        x_min: cols.0 as f64 * res_x,
+        x_max: cols.1 as f64 * res_x,
+        y_min: rows.0 as f64 * res_y,
+        y_max: rows.1 as f64 * res_y,
+        centroid_x: datapoints.iter().map(|(_, col)| *col as f64).sum::<f64>() / count * res_x,
+        centroid_y: datapoints.iter().map(|(row, _)| *row as f64).sum::<f64>() / count * res_y,
+        peak,
+        peak_x: peak_col as f64 * res_x,
+        peak_y: peak_row as f64 * res_y,
+        length_6db: (cols_6db.1 - cols_6db.0 + 1) as f64 * res_x,
+        width_6db: (rows_6db.1 - rows_6db.0 + 1) as f64 * res_y,
+        area_6db: within_6db.len() as f64 * res_x * res_y
+    }
+}
+
+/// Returns the (min, max) row and column of datapoints
+fn bounds(datapoints: &[(usize, usize)]) -> ((usize, usize), (usize, usize)) {
+    datapoints.iter().fold(((usize::MAX, 0), (usize::MAX, 0)), |(rows, cols), &(row, col)| {
+        ((rows.0.min(row), rows.1.max(row)), (cols.0.min(col), cols.1.max(col)))
+    })
+}
+
+/// Returns the maximum finite value of a scan or `NaN` if there is none
+fn scan_max(scan: &Array2<f64>) -> f64 {
+    scan.iter().copied().filter(|value| value.is_finite()).reduce(f64::max).unwrap_or(f64::NAN)
+}
+
+/// Converts indications into CSV with a header line
+///
+/// # Arguments
+/// * `indications`: Detected indications
+pub fn indications_to_csv(indications: &[Indication]) -> String {
+    let mut output = String::from("id,datapoints,area,x_min,x_max,y_min,y_max,centroid_x,centroid_y,peak,peak_x,peak_y,length_6db,width_6db,area_6db\n");
+
+    for indication in indications {
+        output.push_str(&format!("{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            indication.id, indication.datapoints, indication.area, indication.x_min, indication.x_max, indication.y_min, indication.y_max,
+            indication.centroid_x, indication.centroid_y, indication.peak, indication.peak_x, indication.peak_y,
+            indication.length_6db, indication.width_6db, indication.area_6db));
+    }
+
+    output
+}
@@ -0,0 +1,331 @@
+use rocket::{FromForm, FromFormField};
+use serde::{Serialize, Deserialize};
+
+use crate::data::{AScanFilter, SubSet};
+use crate::material::DepthProfile;
+use crate::signal::{AmplitudeMode, Threshold, TofMethod};
+
+/// Interface gate detecting the front-wall echo of an A-Scan
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, FromForm)]
+pub struct InterfaceGate {
+    /// Start index of the search range
+    pub start: usize,
+    /// End index of the search range
+    pub end: usize,
+    /// Threshold which has to be exceeded by the interface echo
+    pub threshold: Threshold
+}
+
+impl InterfaceGate {
+    /// Detects the interface echo inside the search range
+    ///
+    /// The echo is detected on the filtered samples without distance amplitude
+    /// correction and software gain.
+    ///
+    /// # Arguments
+    /// * `a_scan`: Complete filtered and corrected A-Scan
+    /// * `gain`: Gain of the channel
+    /// * `filter`: Filter the A-Scan has been processed with
+    /// * `scratch`: Buffer for the uncorrected search range
+    ///
+    /// # Returns
+    /// The sample index of the first threshold crossing or **None** if
+    /// the threshold isn't exceeded inside the search range
+    pub fn detect(&self, a_scan: &[f64], gain: f64, filter: &AScanFilter, scratch: &mut Vec<f64>) -> Option<usize> {
+        let end = self.end.min(a_scan.len());
+
+        if self.start >= end {
+            return None;
+        }
+
+        scratch.clear();
+        scratch.extend_from_slice(&a_scan[self.start..end]);
+        filter.remove_correction(scratch, self.start);
+
+        TofMethod::Threshold.detect(scratch, self.threshold.linear(gain, false))
+            .map(|position| position.ceil() as usize + self.start)
+    }
+}
+
+/// Unit of the gate positions of a request
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum GateUnit {
+    /// Sample indices
+    #[default]
+    #[field(value = "samples")]
+    Samples,
+    /// Time of the A-Scan axis in µs
+    #[field(value = "us")]
+    Us,
+    /// Depth in mm, converted with the sound velocities of a material (pulse-echo)
+    #[field(value = "mm")]
+    Mm
+}
+
+impl GateUnit {
+    /// Converts a gate position into a sample index
+    ///
+    /// # Arguments
+    /// * `value`: Position in this unit
+    /// * `subset`: Subset settings of the channel
+    /// * `profile`: Depth profile of the material, required for `mm`
+    /// * `relative`: The position is relative to the interface echo, i.e. a
+    ///   duration instead of a time of the A-Scan axis. Depths are then converted
+    ///   with the profile starting at the interface echo.
+    ///
+    /// # Returns
+    /// The nearest sample index
+    ///
+    /// # Errors
+    /// A message is returned if the value isn't finite, the profile is missing
+    /// for `mm` or the position is before the first sample
+    pub fn to_samples(self, value: f64, subset: &SubSet, profile: Option<&DepthProfile>, relative: bool) -> Result<usize, String> {
+        if !value.is_finite() {
+            return Err(String::from("The gate positions have to be finite!"));
+        }
+
+        let time = match self {
+            GateUnit::Samples => None,
+            GateUnit::Us => Some(value),
+            GateUnit::Mm => match profile {
+                Some(profile) => Some(profile.time(value)),
+                None => return Err(String::from("A velocity or a selected material is required for gates in mm!"))
+            }
+        };
+
+        let position = match time {
+            Some(time) if relative => time * 1000.0 / subset.sample_resolution as f64,
+            Some(time) => (time - subset.min_sample_pos as f64) * 1000.0 / subset.sample_resolution as f64,
+            None => value
+        };
+
+        if position.round() < 0.0 {
+            return Err(format!("The gate position {} is before the first sample!", value));
+        }
+
+        Ok(position.round() as usize)
+    }
+}
+
+/// Measurement gate of a C- or D-Scan
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gate {
+    /// Start index, relative to the interface echo if `interface` is set
+    pub start: usize,
+    /// End index, relative to the interface echo if `interface` is set
+    pub end: usize,
+    /// Optional interface gate triggering this gate
+    pub interface: Option<InterfaceGate>
+}
+
+impl Gate {
+    /// Creates a gate with a fixed position
+    ///
+    /// # Arguments
+    /// * `start`: Start index
+    /// * `end`: End index
+    pub fn absolute(start: usize, end: usize) -> Gate {
+        Gate { start, end, interface: None }
+    }
+
+    /// Checks if the gate fits into the A-Scans
+    ///
+    /// # Arguments
+    /// * `samples`: Number of samples per A-Scan
+    ///
+    /// # Errors
+    /// A message is returned if the gate is empty, ends after the last sample or the
+    /// threshold of the interface gate is invalid. Gates following an interface echo
+    /// are checked with the echo at the first sample.
+    pub fn validate(&self, samples: usize) -> Result<(), String> {
+        if self.start >= self.end {
+            return Err(format!("The gate {}..{} is empty!", self.start, self.end));
+        }
+
+        if self.end > samples {
+            return Err(format!("The gate {}..{} exceeds the A-Scan length of {} samples!", self.start, self.end, samples));
+        }
+
+        if let Some(interface) = &self.interface {
+            if interface.start >= interface.end || interface.end > samples {
+                return Err(format!("The interface gate {}..{} doesn't fit into the A-Scan length of {} samples!",
+                    interface.start, interface.end, samples));
+            }
+
+            interface.threshold.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Determines the position of the gate for a single A-Scan
+    ///
+    /// # Arguments
+    /// * `a_scan`: Complete filtered and corrected A-Scan
+    /// * `gain`: Gain of the channel
+    /// * `filter`: Filter the A-Scan has been processed with
+    /// * `scratch`: Buffer for the search range of the interface gate
+    ///
+    /// # Returns
+    /// The absolute start and end index of the gate. **None** is returned if
+    /// the interface echo hasn't been detected or the gate is outside of the A-Scan.
+    pub fn window(&self, a_scan: &[f64], gain: f64, filter: &AScanFilter, scratch: &mut Vec<f64>) -> Option<(usize, usize)> {
+        let (start, end) = match &self.interface {
+            Some(interface) => {
+                let position = interface.detect(a_scan, gain, filter, scratch)?;
+                (position + self.start, (position + self.end).min(a_scan.len()))
+            }
+            None => (self.start, self.end.min(a_scan.len()))
+        };
+
+        if start < end {
+            Some((start, end))
+        }
+        else {
+            None
+        }
+    }
+}
+
+/// Maximum number of windows of a C-Scan stack
+pub const MAX_TIME_SLICES: usize = 512;
+
+/// Consecutive time windows for a stack of C-Scans
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, FromForm)]
+pub struct TimeSlices {
+    /// First sample of the first window
+    pub start: usize,
+    /// First sample after the last window
+    pub end: usize,
+    /// Width of each window in samples
+    pub width: usize,
+    /// Distance between the starts of two windows in samples
+    pub step: usize
+}
+
+impl TimeSlices {
+    /// Checks if the windows fit into the A-Scans
+    ///
+    /// # Arguments
+    /// * `samples`: Number of samples per A-Scan
+    ///
+    /// # Errors
+    /// A message is returned if width or step are zero, no window fits between
+    /// `start` and `end` or there are more than `MAX_TIME_SLICES` windows
+    pub fn validate(&self, samples: usize) -> Result<(), String> {
+        if self.width == 0 || self.step == 0 {
+            return Err(String::from("Width and step have to be positive!"));
+        }
+
+        if self.end > samples || self.width > self.end.saturating_sub(self.start) {
+            return Err(String::from("The time windows don't fit into the A-Scans!"));
+        }
+
+        if self.count() > MAX_TIME_SLICES {
+            return Err(format!("At most {} time windows are allowed!", MAX_TIME_SLICES));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of windows, requires valid slices
+    pub fn count(&self) -> usize {
+        (self.end - self.width - self.start) / self.step + 1
+    }
+
+    /// Returns the start and end index of each window
+    pub fn windows(&self) -> Vec<(usize, usize)> {
+        (self.start..=self.end - self.width).step_by(self.step)
+            .map(|start| (start, start + self.width))
+            .collect()
+    }
+}
+
+/// Gate with a name and its own evaluation settings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NamedGate {
+    /// Name of the gate, e.g. `A`
+    pub name: String,
+    /// Position of the gate
+    #[serde(flatten)]
+    pub gate: Gate,
+    /// Amplitude measure of the gate
+    #[serde(default)]
+    pub mode: AmplitudeMode,
+    /// Time of flight detection method of the gate
+    #[serde(default)]
+    pub method: TofMethod,
+    /// Threshold for the threshold based detection methods
+    #[serde(default)]
+    pub threshold: Threshold
+}
+
+/// Time difference between two named gates (`to - from`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GateDifference {
+    /// Name of the subtracted gate
+    pub from: String,
+    /// Name of the gate the difference is measured to
+    pub to: String
+}
+
+impl GateDifference {
+    /// Returns the name of the difference, e.g. `B-A`
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.to, self.from)
+    }
+}
+
+/// Gate configuration of a channel
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GateConfig {
+    /// List of gates
+    pub gates: Vec<NamedGate>,
+    /// Time differences between gates
+    #[serde(default)]
+    pub differences: Vec<GateDifference>
+}
+
+impl GateConfig {
+    /// Returns the index of a gate
+    ///
+    /// # Arguments
+    /// * `name`: Name of the gate
+    ///
+    /// # Returns
+    /// The position of the gate in `gates` or **None** if no gate has this name
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.gates.iter().position(|gate| gate.name == name)
+    }
+
+    /// Checks the configuration for consistency
+    ///
+    /// # Errors
+    /// A message is returned if a gate name is empty, contains other characters
+    /// than letters, digits and `_`, is used twice, a threshold is invalid or a
+    /// difference refers to an unknown gate
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, gate) in self.gates.iter().enumerate() {
+            if gate.name.is_empty() || !gate.name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!("Invalid gate name '{}'!", gate.name));
+            }
+
+            if self.index_of(&gate.name) != Some(index) {
+                return Err(format!("Gate name {} is used twice!", gate.name));
+            }
+
+            gate.threshold.validate().map_err(|error| format!("Gate {}: {}", gate.name, error))?;
+        }
+
+        for difference in &self.differences {
+            for name in [&difference.from, &difference.to] {
+                if self.index_of(name).is_none() {
+                    return Err(format!("Unknown gate {} in difference!", name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,14 +1,46 @@
 #[macro_use] extern crate rocket;
 
-use std::{sync::Mutex, vec, fs::{File, self}, io::{Write, Cursor, Read}, fmt::Display, ops::Add, path::Path, process::{self}};
-use data::filter_a_scan;
-use ndarray::{s, OwnedRepr, Dim, ArrayBase};
-use rocket::{Config, data::ToByteUnit, Data, State, serde::{json::Json, Serialize}, fs::FileServer, response::status::BadRequest};
+use std::{any::Any, collections::{BTreeMap, HashMap}, sync::{Arc, Mutex}, vec, fs::{File, self}, io::{Write, Cursor, Read}, fmt::Display, ops::Add, path::Path, process::{self}};
+use acceptance::{AcceptanceProfile, AcceptanceResult, AcceptanceScans};
+use amplitude::{AmplitudeUnit, UnitScale};
+use binary::{BinaryArray, ResponseFormat};
+use cache::{CacheSize, ScanCache, MAX_CACHE_BYTES};
+use calibration::{CalibrationResult, VelocityCalibration};
+use correction::CorrectionCurve;
+use data::{AScanFilter, Samples};
+use defect::{DefectConfig, Indication};
+use gate::{Gate, GateConfig, GateUnit, InterfaceGate, TimeSlices};
+use material::{DepthProfile, MaterialLibrary, MaterialStack};
+use roi::{Roi, RoiMask};
+use section::{Polyline, ScanAxis};
+use signal::{AmplitudeMode, DecibelReference, DecibelReferenceKind, Threshold, ThresholdUnit, TofMethod};
+use statistics::{ScanStatistics, Statistics, StatisticsSource, DEFAULT_BINS, DEFAULT_PERCENTILES};
+use thickness::ThicknessConfig;
+use tile::TileAggregation;
+use volume::{FilteredVolume, VolumeStatus, VolumeStore};
+use ndarray::{s, Array2, ArrayView1, OwnedRepr, Dim, ArrayBase};
+use rocket::{Config, data::ToByteUnit, Data, State, serde::{json::Json, Deserialize, Serialize}, fs::FileServer, response::status::BadRequest};
 use rocket_dyn_templates::{context, Template};
 use zip::write::SimpleFileOptions;
 
+mod acceptance;
+mod amplitude;
+mod binary;
+mod cache;
+mod calibration;
+mod correction;
 mod data;
+mod defect;
+mod gate;
+mod material;
+mod roi;
+mod section;
+mod signal;
+mod statistics;
 mod test;
+mod thickness;
+mod tile;
+mod volume;
 
 /// Response struct for A-Scans
 #[derive(Serialize)]
@@ -20,290 +52,2760 @@ struct AScanJson {
     /// Time axis resolution
     time_step: f32,
     /// Filtered A-Scan
-    filtered_scan: Vec<f64>
+    filtered_scan: Vec<f64>,
+    /// Unit of `scan` and `filtered_scan`
+    unit: AmplitudeUnit,
+    /// Sample indices of `scan` if it has been downsampled, relative to `time_start`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scan_indices: Option<Vec<usize>>,
+    /// Sample indices of `filtered_scan` if it has been downsampled, relative to `time_start`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filtered_indices: Option<Vec<usize>>
+}
+
+/// Request for the A-Scans of several positions
+#[derive(Deserialize)]
+struct AScanBatch {
+    /// Positions as (column, row) indices, at most `MAX_BATCH_POSITIONS`
+    positions: Vec<(usize, usize)>,
+    /// Sample range and downsampling of each A-Scan
+    #[serde(flatten)]
+    range: AScanRange,
+    /// The mean A-Scan of all positions should be returned as well
+    #[serde(default)]
+    average: bool
+}
+
+/// Maximum number of positions of an A-Scan batch
+const MAX_BATCH_POSITIONS: usize = 256;
+
+/// Response struct for the A-Scans of several positions
+#[derive(Serialize)]
+struct AScanBatchJson {
+    /// A-Scan of each position
+    a_scans: Vec<AScanJson>,
+    /// Mean A-Scan of all positions if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average: Option<AScanJson>
+}
+
+/// Response struct for B-Scans
+#[derive(Serialize)]
+struct BScanJson {
+    /// Processed A-Scans, one row for each position
+    scan: Vec<Vec<f64>>,
+    /// Position (path length) of each row in mm
+    position: Vec<f64>,
+    /// Start time of the A-Scans
+    time_start: f32,
+    /// Time axis resolution
+    time_step: f32,
+    /// Depth of each sample in mm if a material has been selected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<Vec<f64>>
+}
+
+/// Response struct for the scans of a single gate
+#[derive(Serialize)]
+struct GateScanJson {
+    /// Name of the gate
+    name: String,
+    /// C-Scan of the gate
+    c_scan: Vec<Vec<f64>>,
+    /// D-Scan of the gate in µs or, if requested, in mm
+    d_scan: Vec<Vec<f64>>
+}
+
+/// Response struct for a time difference between two gates
+#[derive(Serialize)]
+struct GateDifferenceJson {
+    /// Name of the difference, e.g. `B-A`
+    name: String,
+    /// Time difference of each datapoint in µs
+    scan: Vec<Vec<f64>>
+}
+
+/// Response struct for the scans of all gates of a channel
+#[derive(Serialize)]
+struct GateScansJson {
+    /// Scans of each gate
+    gates: Vec<GateScanJson>,
+    /// Configured time differences
+    differences: Vec<GateDifferenceJson>
+}
+
+/// Response struct for a wall thickness map
+#[derive(Serialize)]
+struct ThicknessJson {
+    /// Wall thickness of each datapoint in mm
+    thickness: Vec<Vec<f64>>,
+    /// Statistics of the wall thickness
+    statistics: Statistics
+}
+
+/// Response struct for the statistics of a scan
+#[derive(Serialize)]
+struct StatisticsJson {
+    /// Unit of the values, e.g. `dB` or `mm`
+    unit: &'static str,
+    /// Statistics and histogram of the scan
+    #[serde(flatten)]
+    scan: ScanStatistics
+}
+
+/// Response struct for the detected indications of a C-Scan
+#[derive(Serialize)]
+struct DefectsJson {
+    /// Absolute threshold of the segmentation in dB
+    threshold: f64,
+    /// Detected indications
+    indications: Vec<Indication>
+}
+
+/// Additional information of a C-Scan stack
+#[derive(Serialize)]
+struct CScanStackInfo {
+    /// Start time of each window in µs
+    window_start: Vec<f64>,
+    /// Width of the windows in µs
+    window_width: f64,
+    /// Scaling of the horizontal axis
+    x_step: f32,
+    /// Scaling of the vertical axis
+    y_step: f32,
+    /// Amplitude measure of the C-Scans
+    mode: AmplitudeMode
+}
+
+/// Axis scaling of a binary scan response
+#[derive(Serialize)]
+struct ScanInfo {
+    /// Distance between two columns in mm
+    x_step: f32,
+    /// Distance between two rows in mm
+    y_step: f32
+}
+
+/// Response containing a 2-D scan in the requested format
+#[derive(Responder)]
+enum ScanResponse {
+    /// Scan as nested JSON lists, `NaN` values are `null`
+    Json(Json<Vec<Vec<f64>>>),
+    /// Scan as `BinaryArray` of shape `[rows, columns]`
+    Binary(BinaryArray<ScanInfo>)
+}
+
+/// Position and scaling of a C-Scan tile
+#[derive(Serialize)]
+struct TileInfo {
+    /// Level of the pyramid, `0` is the full resolution
+    level: usize,
+    /// Number of levels of the pyramid
+    levels: usize,
+    /// Column index of the tile
+    tx: usize,
+    /// Row index of the tile
+    ty: usize,
+    /// Number of tile columns of the level
+    tiles_x: usize,
+    /// Number of tile rows of the level
+    tiles_y: usize,
+    /// Full resolution column of the first datapoint
+    x_start: usize,
+    /// Full resolution row of the first datapoint
+    y_start: usize,
+    /// Distance between two columns of the level in mm
+    x_step: f32,
+    /// Distance between two rows of the level in mm
+    y_step: f32,
+    /// Aggregation of the combined datapoints
+    aggregation: TileAggregation
+}
+
+/// Response struct for a C-Scan tile
+#[derive(Serialize)]
+struct TileJson {
+    /// Position and scaling of the tile
+    #[serde(flatten)]
+    info: TileInfo,
+    /// Values of the tile, `NaN` values are `null`
+    tile: Vec<Vec<f64>>
+}
+
+/// Response containing a C-Scan tile in the requested format
+#[derive(Responder)]
+enum TileResponse {
+    /// Tile with its information as JSON
+    Json(Json<TileJson>),
+    /// Tile as `BinaryArray` of shape `[rows, columns]`
+    Binary(BinaryArray<TileInfo>)
 }
 
 /// Structure for the export config
 #[derive(Serialize)]
 struct ExportHeader {
     /// List containing the aperture start and end
+    /// (relative to the interface echo if `interface` is set)
     aperture: Vec<f32>,
+    /// Interface gate triggering the aperture
+    interface: Option<InterfaceGate>,
+    /// Time of the first sample of the A-Scans in µs. The times of flight in `d_scan.csv` and
+    /// `gate_<name>_d_scan.csv` are relative to the first sample, this offset yields the absolute time.
+    time_offset: f32,
     /// Scaling of the horizontal axis
     x_step: f32,
     /// Scaling of the vertical axis
     y_step: f32,
     /// Gain of the current channel
-    gain: f64
+    gain: f64,
+    /// Software gain of the current channel in dB
+    software_gain: f64,
+    /// Voltage of full screen height of the current channel, if it has been set
+    voltage_range: Option<f64>,
+    /// Unit of the additional amplitude scans
+    unit: AmplitudeUnit,
+    /// Amplitude measure used for the C-Scans
+    mode: AmplitudeMode,
+    /// Reference of the dB scans
+    db_reference: DecibelReference,
+    /// Time of flight detection method used for the D-Scan
+    tof_method: TofMethod,
+    /// Threshold of the time of flight detection
+    threshold: Threshold,
+    /// Named gates of the channel
+    gates: GateConfig,
+    /// Configuration of the wall thickness map
+    thickness: Option<ThicknessConfig>,
+    /// Region of interest the scans are restricted to
+    roi: Option<Roi>,
+    /// Configuration of the defect detection
+    defects: Option<DefectConfig>,
+    /// Distance amplitude correction of the channel
+    correction: Option<CorrectionCurve>,
+    /// Material the depth scans are based on
+    material: Option<DepthProfile>,
+    /// Statistics of the wall thickness map in mm
+    thickness_statistics: Option<Statistics>
 }
 
 /// Internal handler for the loaded dataset
 struct DataHandler {
     /// Mutex for the (loaded) dataset
-    dataset: Mutex<Option<data::UsData>>
+    dataset: Mutex<Option<data::UsData>>,
+    /// Gate configuration of each channel
+    gates: Mutex<HashMap<usize, GateConfig>>,
+    /// Distance amplitude correction of each channel
+    corrections: Mutex<HashMap<usize, CorrectionCurve>>,
+    /// Software gain in dB of each channel
+    software_gains: Mutex<HashMap<usize, f64>>,
+    /// Voltage of full screen height in V of each channel
+    voltage_ranges: Mutex<HashMap<usize, f64>>,
+    /// Material selected for the loaded dataset
+    material: Mutex<Option<DepthProfile>>,
+    /// Computed scans of the loaded dataset
+    cache: Mutex<ScanCache>,
+    /// Precomputed filtered volumes of the loaded dataset
+    volumes: Arc<Mutex<VolumeStore>>
+}
+
+impl DataHandler {
+    /// Returns the gate configuration of a channel
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// 
+    /// # Returns
+    /// A copy of the gate configuration. If no gates have been set
+    /// the configuration is empty.
+    /// 
+    /// # Errors
+    /// An error code is returned if the gate configurations can't be locked
+    fn gate_config(&self, channel: usize) -> Result<GateConfig, BadRequest<String>> {
+        match self.gates.lock() {
+            Ok(gates) => Ok(gates.get(&channel).cloned().unwrap_or_default()),
+            Err(error) => {
+                println!("{}", error);
+                Err(BadRequest(String::from("Failed to lock gates")))
+            }
+        }
+    }
+
+    /// Returns the distance amplitude correction of a channel
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// 
+    /// # Returns
+    /// A copy of the correction curve or **None** if no curve has been set
+    /// 
+    /// # Errors
+    /// An error code is returned if the correction curves can't be locked
+    fn correction(&self, channel: usize) -> Result<Option<CorrectionCurve>, BadRequest<String>> {
+        match self.corrections.lock() {
+            Ok(corrections) => Ok(corrections.get(&channel).cloned()),
+            Err(error) => {
+                println!("{}", error);
+                Err(BadRequest(String::from("Failed to lock correction curves")))
+            }
+        }
+    }
+
+    /// Returns the reference of the dB values of a channel
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// * `requested`: Requested reference
+    /// 
+    /// # Returns
+    /// Full screen height if the channel is corrected by a DAC curve, else the requested reference
+    /// 
+    /// # Errors
+    /// An error code is returned if the correction curves can't be locked
+    fn decibel_reference(&self, channel: usize, requested: DecibelReference) -> Result<DecibelReference, BadRequest<String>> {
+        Ok(match self.correction(channel)? {
+            Some(curve) => curve.decibel_reference(requested),
+            None => requested
+        })
+    }
+
+    /// Returns the software gain of a channel
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// 
+    /// # Returns
+    /// The gain in dB, `0` if no gain has been set
+    /// 
+    /// # Errors
+    /// An error code is returned if the software gains can't be locked
+    fn software_gain(&self, channel: usize) -> Result<f64, BadRequest<String>> {
+        match self.software_gains.lock() {
+            Ok(gains) => Ok(gains.get(&channel).copied().unwrap_or(0.0)),
+            Err(error) => {
+                println!("{}", error);
+                Err(BadRequest(String::from("Failed to lock software gains")))
+            }
+        }
+    }
+
+    /// Returns the voltage range of a channel
+    /// 
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// 
+    /// # Returns
+    /// The voltage of full screen height in V or **None** if no range has been set
+    /// 
+    /// # Errors
+    /// An error code is returned if the voltage ranges can't be locked
+    fn voltage_range(&self, channel: usize) -> Result<Option<f64>, BadRequest<String>> {
+        match self.voltage_ranges.lock() {
+            Ok(ranges) => Ok(ranges.get(&channel).copied()),
+            Err(error) => {
+                println!("{}", error);
+                Err(BadRequest(String::from("Failed to lock voltage ranges")))
+            }
+        }
+    }
+
+    /// Returns the material selected for the loaded dataset
+    /// 
+    /// # Returns
+    /// A copy of the depth profile or **None** if no material has been selected
+    /// 
+    /// # Errors
+    /// An error code is returned if the material can't be locked
+    fn material(&self) -> Result<Option<DepthProfile>, BadRequest<String>> {
+        match self.material.lock() {
+            Ok(material) => Ok(material.clone()),
+            Err(error) => {
+                println!("{}", error);
+                Err(BadRequest(String::from("Failed to lock material")))
+            }
+        }
+    }
+
+    /// Returns the depth profile of a request
+    /// 
+    /// # Arguments
+    /// * `velocity`: Sound velocity in m/s of the request, overrides the selected material
+    /// 
+    /// # Returns
+    /// A homogeneous profile of the velocity, the selected material or **None**
+    /// if neither is available
+    /// 
+    /// # Errors
+    /// An error code is returned if the velocity isn't positive or the material can't be locked
+    fn depth_profile(&self, velocity: Option<f64>) -> Result<Option<DepthProfile>, BadRequest<String>> {
+        match velocity {
+            Some(velocity) if velocity.is_finite() && velocity > 0.0 => Ok(Some(DepthProfile::homogeneous(velocity))),
+            Some(_) => Err(BadRequest(String::from("The velocity has to be positive!"))),
+            None => self.material()
+        }
+    }
+
+    /// Returns the depth profile of a request for depth scans
+    /// 
+    /// # Arguments
+    /// * `depth`: The scans should be returned in mm
+    /// * `velocity`: Sound velocity in m/s of the request, overrides the selected material
+    /// 
+    /// # Returns
+    /// The depth profile if `depth` is set, else **None**
+    /// 
+    /// # Errors
+    /// An error code is returned if depths are requested without a velocity or a selected material
+    fn depth_scan_profile(&self, depth: Option<bool>, velocity: Option<f64>) -> Result<Option<DepthProfile>, BadRequest<String>> {
+        match depth {
+            Some(true) => self.depth_profile(velocity)?
+                .ok_or(BadRequest(String::from("A velocity or a selected material is required for depth scans!")))
+                .map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    /// Returns the filter of a channel including its distance amplitude correction
+    /// and software gain
+    /// 
+    /// # Arguments
+    /// * `data`: Loaded dataset
+    /// * `channel`: Channel index
+    fn filter(&self, data: &data::UsData, channel: usize) -> AScanFilter {
+        let filter = AScanFilter::load().with_gain(self.software_gain(channel).unwrap_or(0.0));
+        let curve = self.correction(channel).ok().flatten();
+
+        match (curve, data.get_channel_subset(channel), data.get_channel(channel)) {
+            (Some(curve), Some(subset), Some(samples)) => filter.with_correction(&curve, subset, samples.shape()[2]),
+            _ => filter
+        }
+    }
+
+    /// Returns a cached scan or computes and caches it
+    /// 
+    /// # Arguments
+    /// * `kind`: Kind of the scan, e.g. `c_scan`
+    /// * `parameters`: All parameters the scan depends on
+    /// * `compute`: Computation of the scan
+    /// 
+    /// # Returns
+    /// The scan or **None** if it can't be computed
+    fn cached<T, P, F>(&self, kind: &str, parameters: &P, compute: F) -> Option<Arc<T>>
+        where T: Any + Send + Sync + CacheSize, P: Serialize, F: FnOnce() -> Option<T> {
+        let key = match self.cache.lock() {
+            Ok(cache) => {
+                let key = cache.key(kind, parameters);
+
+                if let Some(scan) = cache.get(&key) {
+                    return Some(scan);
+                }
+                key
+            }
+            Err(_) => return compute().map(Arc::new)
+        };
+
+        let scan = compute()?;
+
+        match self.cache.lock() {
+            Ok(mut cache) => Some(cache.insert(key, scan)),
+            Err(_) => Some(Arc::new(scan))
+        }
+    }
+
+    /// Returns the precomputed volume of a channel
+    /// 
+    /// If the channel has been requested for precomputation and its volume is
+    /// missing or has been computed with a different filter, a new computation is started.
+    /// 
+    /// # Arguments
+    /// * `data`: Loaded dataset
+    /// * `channel`: Channel index
+    /// * `filter`: Current filter
+    /// 
+    /// # Returns
+    /// The volume if it has been computed with the current filter, else **None**
+    fn volume(&self, data: &data::UsData, channel: usize, filter: &AScanFilter) -> Option<Arc<FilteredVolume>> {
+        let mut store = self.volumes.lock().ok()?;
+        let filter_key = filter.key();
+
+        if let (Some(with_envelope), Some(channel_data)) = (store.needs_update(channel, &filter_key), data.get_channel(channel)) {
+            store.start(&self.volumes, channel, channel_data.clone(), filter.clone(), with_envelope);
+        }
+
+        store.volume(channel, &filter_key)
+    }
+
+    /// Drops the volumes of the previous dataset and restarts the precomputation
+    /// of the requested channels
+    /// 
+    /// # Arguments
+    /// * `data`: Newly loaded dataset
+    fn reset_volumes(&self, data: Option<&data::UsData>) {
+        let channels = match self.volumes.lock() {
+            Ok(mut store) => {
+                store.reset();
+                store.requested_channels()
+            }
+            Err(_) => vec![]
+        };
+
+        if let Some(loaded_data) = data {
+            for channel in channels {
+                self.volume(loaded_data, channel, &self.filter(loaded_data, channel));
+            }
+        }
+    }
+
+    /// Sets the dataset the cached scans are computed from
+    /// 
+    /// # Arguments
+    /// * `identity`: Identity of the loaded dataset
+    fn set_cache_dataset(&self, identity: u64) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.set_dataset(identity);
+        }
+    }
+
+    /// Removes all cached scans
+    fn clear_cache(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear();
+        }
+    }
+
+    /// Removes the material selection of the previous dataset
+    fn clear_material(&self) {
+        if let Ok(mut material) = self.material.lock() {
+            *material = None;
+        }
+    }
 }
 
 /// Converts a 2-D-Array into a CSV representation
 /// 
 /// # Arguments
-/// * `array`: 2-D-Array with data
-/// * `start`: Additional offset for the data
-/// * `scale`: Additional scaling of the data
+/// * `array`: 2-D-Array with data
+/// * `start`: Additional offset for the data
+/// * `scale`: Additional scaling of the data
+/// 
+/// # Returns
+/// A `String` containing `start + data * scale` for all values in CSV format
+fn array_to_csv<T>(array: ArrayBase<OwnedRepr<T>, Dim<[usize; 2]>>, start: f64, scale: f64) -> String 
+    where T: Clone + Copy + Display + Into<f64> {
+    let mut output = String::new();
+    for row in array.outer_iter() {
+        let values = row.to_vec();
+        let first_value = format!("{}", values[0].into() * scale + start);
+        output = output.add(&first_value);
+        for value in values {
+            output = output.add(",").add(&format!("{}", value.into() * scale + start));
+        }
+
+        output = output.add("\n");
+    }
+
+    output
+}
+
+/// Read a CSV file and return the data as a 2D vector
+/// 
+/// # Arguments
+/// * `csv`: CSV file as string
+/// 
+/// # Returns
+/// If the data has been parsed correctly a 2D vector will be returned.
+/// 
+/// # Failures
+/// If a value can't be mapped into a Float variable `None` will be returned.
+fn csv_to_array(csv: &str) -> Option<Vec<Vec<f32>>> {
+    let mut array = vec![];
+
+    for line in csv.lines() {
+        let mut new_row = vec![];
+
+        for element in line.split(',') {
+            match element.parse() {
+                Ok(number) => {
+                    new_row.push(number);
+                }
+                Err(_) => {
+                    return None;
+                }
+            }
+        }
+
+        array.push(new_row);
+    }
+
+    Some(array)
+}
+
+/// Converts a Vector into a List of Lists
+/// 
+/// # Arguments
+/// * `vector`: Vector with data
+/// * `cols`: Max Length for the inner lists
+/// 
+/// # Returns
+/// A List of Lists with `cols` values
+fn vec_to_2d_list<T>(vector: &[T], cols: usize) -> Vec<Vec<T>>
+    where T: Clone {
+    let mut scan = vec![];
+
+    for (index, value) in vector.iter().enumerate() {
+        let row = index / cols;
+
+        if row >= scan.len() {
+            scan.push(vec![]);
+        }
+
+        let current_row_list = scan.last_mut().unwrap();
+        current_row_list.push(value.clone());
+    }
+
+    scan
+}
+
+/// Calculates the configured time differences between gates
+/// 
+/// # Arguments
+/// * `config`: Gate configuration
+/// * `scans`: Scans of the gates in the order of `config.gates`
+/// 
+/// # Returns
+/// A list containing the name and the time difference of each configured difference
+fn gate_differences(config: &GateConfig, scans: &[data::GateScan]) -> Vec<(String, Array2<f64>)> {
+    config.differences.iter().map(|difference| {
+        let from = &scans[config.index_of(&difference.from).unwrap()].time;
+        let to = &scans[config.index_of(&difference.to).unwrap()].time;
+
+        (difference.name(), to - from)
+    }).collect()
+}
+
+/// Returns the position of the first datapoint of a tile
+/// 
+/// # Arguments
+/// * `index`: Column or row index of the tile
+/// * `factor`: Downsampling factor of the level
+/// 
+/// # Returns
+/// The column or row index of the first datapoint in the full resolution scan
+/// 
+/// # Errors
+/// An error code is returned if the position overflows
+fn tile_start(index: usize, factor: usize) -> Result<usize, BadRequest<String>> {
+    index.checked_mul(tile::TILE_SIZE).and_then(|start| start.checked_mul(factor))
+        .ok_or(BadRequest(format!("Tile index {} is too large!", index)))
+}
+
+/// Returns the cached linear C-Scan of a channel
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler for the data
+/// * `data`: Loaded dataset
+/// * `channel`: Channel index
+/// * `gate`: Gate defining the aperture
+/// * `mode`: Amplitude measure inside the aperture
+/// * `roi`: Optional region of interest, datapoints outside are `NaN`
+/// 
+/// # Returns
+/// The C-Scan or **None** if the channel hasn't been recorded
+fn cached_c_scan(data_accessor: &DataHandler, data: &data::UsData, channel: usize, gate: &Gate, mode: AmplitudeMode, roi: Option<&RoiMask>) -> Option<Arc<Array2<f64>>> {
+    let filter = data_accessor.filter(data, channel);
+    let volume = data_accessor.volume(data, channel, &filter);
+    let samples = Samples::of(&filter, volume.as_deref());
+
+    data_accessor.cached("c_scan", &(channel, gate, mode, samples.key(), roi.map(|roi| &roi.roi)),
+        || data.c_scan(channel, gate, samples, mode, roi.map(|roi| &roi.mask)))
+}
+
+/// Returns the cached D-Scan of a channel
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler for the data
+/// * `data`: Loaded dataset
+/// * `channel`: Channel index
+/// * `gate`: Gate defining the aperture
+/// * `method`: Time of flight detection method
+/// * `threshold`: Threshold for the threshold based methods
+/// * `roi`: Optional region of interest, datapoints outside are `NaN`
+/// 
+/// # Returns
+/// The D-Scan in µs or **None** if the channel hasn't been recorded
+#[allow(clippy::too_many_arguments)]
+fn cached_d_scan(data_accessor: &DataHandler, data: &data::UsData, channel: usize, gate: &Gate, method: TofMethod, threshold: Threshold,
+    roi: Option<&RoiMask>) -> Option<Arc<Array2<f64>>> {
+    let filter = data_accessor.filter(data, channel);
+    let volume = data_accessor.volume(data, channel, &filter);
+    let samples = Samples::of(&filter, volume.as_deref());
+
+    data_accessor.cached("d_scan", &(channel, gate, method, threshold, samples.key(), roi.map(|roi| &roi.roi)),
+        || data.d_scan(channel, gate, samples, method, threshold, roi.map(|roi| &roi.mask)))
+}
+
+/// Returns the cached scans of the named gates of a channel
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler for the data
+/// * `data`: Loaded dataset
+/// * `channel`: Channel index
+/// * `config`: Gate configuration of the channel
+/// * `roi`: Optional region of interest, datapoints outside are `NaN`
+/// 
+/// # Returns
+/// The linear C- and D-Scan of each gate or **None** if the channel hasn't been recorded
+fn cached_gate_scans(data_accessor: &DataHandler, data: &data::UsData, channel: usize, config: &GateConfig, roi: Option<&RoiMask>) -> Option<Arc<Vec<data::GateScan>>> {
+    let filter = data_accessor.filter(data, channel);
+    let volume = data_accessor.volume(data, channel, &filter);
+    let samples = Samples::of(&filter, volume.as_deref());
+
+    data_accessor.cached("gate_scans", &(channel, &config.gates, samples.key(), roi.map(|roi| &roi.roi)),
+        || data.gate_scans(channel, &config.gates, samples, roi.map(|roi| &roi.mask)))
+}
+
+/// Detects the indications of a C-Scan
+/// 
+/// # Arguments
+/// * `config`: Validated defect detection configuration
+/// * `c_scan_db`: C-Scan in dB
+/// * `data`: Loaded dataset providing the axis scaling
+fn detect_defects(config: &DefectConfig, c_scan_db: &Array2<f64>, data: &data::UsData) -> DefectsJson {
+    let threshold = config.threshold_db(c_scan_db);
+
+    DefectsJson {
+        threshold,
+        indications: defect::detect(c_scan_db, threshold, data.header.res_x.into(), data.header.res_y.into(), config.min_area.unwrap_or(0.0))
+    }
+}
+
+/// Resolves an optional region of interest on the loaded dataset
+/// 
+/// # Arguments
+/// * `data`: Loaded dataset
+/// * `roi`: Requested region of interest
+/// 
+/// # Returns
+/// The mask of the region or **None** if no region has been requested
+/// 
+/// # Errors
+/// Returns a `BadRequest` if the region is invalid
+fn roi_mask(data: &data::UsData, roi: Option<Roi>) -> Result<Option<RoiMask>, BadRequest<String>> {
+    roi.map(|roi| roi.resolve(data.header.samples_y.into(), data.header.samples_x.into(), data.header.res_x.into(), data.header.res_y.into()))
+        .transpose().map_err(BadRequest)
+}
+
+/// Returns the conversion of the amplitudes of a channel into a unit
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler for the data
+/// * `channel`: Channel index
+/// * `unit`: Requested unit (default: `normalized`)
+/// 
+/// # Errors
+/// Returns a `BadRequest` if volts are requested and no voltage range has been set for the channel
+fn unit_scale(data_accessor: &DataHandler, channel: usize, unit: Option<AmplitudeUnit>) -> Result<UnitScale, BadRequest<String>> {
+    unit.unwrap_or_default().scale(data_accessor.voltage_range(channel)?).map_err(BadRequest)
+}
+
+/// Converts a D-Scan into depths
+/// 
+/// # Arguments
+/// * `scan`: D-Scan with times of flight in µs
+/// * `profile`: Depth profile of the material
+/// 
+/// # Returns
+/// The depth of each datapoint in mm, `NaN` is kept
+fn depth_scan(scan: &Array2<f64>, profile: &DepthProfile) -> Array2<f64> {
+    scan.mapv(|time| profile.depth(time))
+}
+
+/// Converts a D-Scan into times relative to the first sample of the A-Scans
+/// 
+/// # Arguments
+/// * `scan`: D-Scan with absolute times of flight in µs
+/// * `subset`: Subset settings of the channel
+/// 
+/// # Returns
+/// The time of each datapoint after the first sample in µs, `NaN` is kept
+fn relative_d_scan(scan: &Array2<f64>, subset: &data::SubSet) -> Array2<f64> {
+    scan.mapv(|time| time - subset.min_sample_pos as f64)
+}
+
+/// Returns the depth of consecutive samples
+/// 
+/// # Arguments
+/// * `subset`: Subset settings of the channel
+/// * `start`: Index of the first sample
+/// * `count`: Number of samples
+/// * `profile`: Depth profile of the selected material
+/// 
+/// # Returns
+/// The depth of each sample in mm or **None** if no material has been selected
+fn sample_depths(subset: &data::SubSet, start: usize, count: usize, profile: Option<&DepthProfile>) -> Option<Vec<f64>> {
+    profile.map(|profile| (start..start + count).map(|position| profile.depth(subset.sample_time(position as f64))).collect())
+}
+
+/// Creates the response of a 2-D scan
+/// 
+/// # Arguments
+/// * `scan`: Scan of shape `[rows, columns]`
+/// * `data`: Loaded dataset
+/// * `format`: Requested response format
+/// 
+/// # Returns
+/// The scan as JSON lists or as `BinaryArray` with `f32` values
+fn scan_response(scan: Array2<f64>, data: &data::UsData, format: ResponseFormat) -> ScanResponse {
+    match format {
+        ResponseFormat::Json => ScanResponse::Json(Json(vec_to_2d_list(&scan.into_raw_vec_and_offset().0, data.header.samples_x.into()))),
+        ResponseFormat::Binary => ScanResponse::Binary(BinaryArray {
+            shape: scan.shape().to_vec(),
+            data: scan.iter().map(|value| *value as f32).collect(),
+            info: ScanInfo { x_step: data.header.res_x, y_step: data.header.res_y }
+        })
+    }
+}
+
+/// Creates the detection threshold from the request parameters
+/// 
+/// # Arguments
+/// * `value`: Requested threshold value
+/// * `unit`: Requested threshold unit
+/// * `db_ref`: Requested reference of a dB threshold
+/// * `db_value`: Reference value for `db_ref=user`
+/// 
+/// # Returns
+/// The requested `Threshold` with the default values for missing parameters
+/// 
+/// # Errors
+/// An error code is returned if the threshold is invalid
+fn get_threshold(value: Option<f64>, unit: Option<ThresholdUnit>, db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>) -> Result<Threshold, BadRequest<String>> {
+    let default = Threshold::default();
+
+    let threshold = Threshold {
+        value: value.unwrap_or(default.value),
+        unit: unit.unwrap_or(default.unit),
+        db_ref: db_ref.unwrap_or(default.db_ref),
+        db_value
+    };
+
+    threshold.validate().map_err(BadRequest)?;
+    Ok(threshold)
+}
+
+/// Creates the measurement gate from the request parameters
+/// 
+/// # Arguments
+/// * `data`: Loaded dataset
+/// * `channel`: Channel index
+/// * `start`: Start of the aperture
+/// * `end`: End of the aperture
+/// * `iface`: Optional interface gate
+/// * `unit`: Unit of `start` and `end` (default: `samples`)
+/// * `profile`: Depth profile for gates in mm
+/// 
+/// # Returns
+/// A `Gate` with fixed position or, if an interface gate is provided,
+/// a gate following the detected interface echo with `start` and `end`
+/// relative to the echo
+/// 
+/// # Errors
+/// Returns a `BadRequest` if the channel hasn't been recorded, the positions can't
+/// be converted or the gate doesn't fit into the A-Scans
+fn get_gate(data: &data::UsData, channel: usize, start: f64, end: f64, iface: Option<InterfaceGate>, unit: Option<GateUnit>,
+    profile: Option<&DepthProfile>) -> Result<Gate, BadRequest<String>> {
+    let (subset, samples) = match (data.get_channel_subset(channel), data.get_channel(channel)) {
+        (Some(subset), Some(samples)) => (subset, samples.shape()[2]),
+        _ => return Err(BadRequest(String::from("The channel hasn't been recorded!")))
+    };
+
+    let unit = unit.unwrap_or_default();
+    let relative = iface.is_some();
+    let start = unit.to_samples(start, subset, profile, relative).map_err(BadRequest)?;
+    let end = unit.to_samples(end, subset, profile, relative).map_err(BadRequest)?;
+
+    let gate = match iface {
+        Some(interface) => Gate { start, end, interface: Some(interface) },
+        None => Gate::absolute(start, end)
+    };
+
+    gate.validate(samples).map_err(BadRequest)?;
+    Ok(gate)
+}
+
+/// Sample range, downsampling and unit of requested A-Scans
+#[derive(Clone, Copy, Default, Deserialize, FromForm)]
+struct AScanRange {
+    /// First sample of the returned range (default: `0`)
+    start: Option<usize>,
+    /// First sample after the returned range (default: number of samples)
+    end: Option<usize>,
+    /// Maximum number of values per trace
+    max_points: Option<usize>,
+    /// Unit of the samples (default: `normalized`)
+    unit: Option<AmplitudeUnit>
+}
+
+impl AScanRange {
+    /// Resolves the sample range
+    /// 
+    /// # Arguments
+    /// * `samples`: Number of samples per A-Scan
+    /// 
+    /// # Returns
+    /// The start and end index of the range
+    /// 
+    /// # Errors
+    /// A message is returned if the range is empty or `max_points` is less than `2`
+    fn bounds(&self, samples: usize) -> Result<(usize, usize), BadRequest<String>> {
+        let (start, end) = (self.start.unwrap_or(0), self.end.unwrap_or(samples).min(samples));
+
+        if start >= end {
+            return Err(BadRequest(String::from("The sample range is empty!")));
+        }
+
+        if self.max_points.is_some_and(|points| points < 2) {
+            return Err(BadRequest(String::from("At least 2 points are required!")));
+        }
+
+        Ok((start, end))
+    }
+}
+
+/// Returns the raw and filtered samples of an A-Scan
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler for the data
+/// * `data`: Loaded dataset
+/// * `c`: Channel index
+/// * `x`: Column index
+/// * `y`: Row index
+/// 
+/// # Errors
+/// An error code is returned if the channel hasn't been recorded or the position is outside of the scan
+fn a_scan_traces(data_accessor: &DataHandler, data: &data::UsData, c: usize, x: usize, y: usize) -> Result<(Vec<f64>, Vec<f64>), BadRequest<String>> {
+    let channel = data.get_channel(c).ok_or(BadRequest(String::from("Channel not recorded!")))?;
+    let (rows, cols, _) = channel.dim();
+
+    if y >= rows || x >= cols {
+        return Err(BadRequest(String::from("Position outside of the scan!")));
+    }
+
+    let a_scan = channel.slice(s![y, x, ..]).to_vec();
+
+    let filter = data_accessor.filter(data, c);
+    let filtered_scan = match data_accessor.volume(data, c, &filter) {
+        Some(volume) => volume.a_scan(y, x, false),
+        None => filter.apply(ArrayView1::from(&a_scan))
+    };
+
+    Ok((a_scan, filtered_scan))
+}
+
+/// Creates the response of an A-Scan
+/// 
+/// # Arguments
+/// * `data`: Loaded dataset
+/// * `c`: Channel index
+/// * `traces`: Raw and filtered samples
+/// * `range`: Requested sample range and downsampling
+/// 
+/// # Errors
+/// An error code is returned if the sample range or the unit is invalid
+fn a_scan_json(data_accessor: &DataHandler, data: &data::UsData, c: usize, traces: (Vec<f64>, Vec<f64>), range: AScanRange) -> Result<AScanJson, BadRequest<String>> {
+    let (a_scan, filtered_scan) = traces;
+    let (start, end) = range.bounds(a_scan.len())?;
+    let channel_subset = data.get_channel_subset(c).expect("Subset not found!");
+    let unit = range.unit.unwrap_or_default();
+    let scale = unit_scale(data_accessor, c, Some(unit))?;
+
+    // selects the values of a trace and their indices if it has to be reduced
+    let reduce = |trace: &[f64]| match range.max_points {
+        Some(points) if trace.len() > points => {
+            let indices = signal::min_max_indices(trace, points);
+            (indices.iter().map(|index| scale.sample(trace[*index])).collect(), Some(indices))
+        }
+        _ => (trace.iter().map(|value| scale.sample(*value)).collect(), None)
+    };
+
+    let (scan, scan_indices) = reduce(&a_scan[start..end]);
+    let (filtered_scan, filtered_indices) = reduce(&filtered_scan[start..end]);
+
+    Ok(AScanJson { 
+        scan,
+        time_start: channel_subset.sample_time(start as f64) as f32,
+        time_step: channel_subset.sample_resolution,
+        filtered_scan,
+        unit,
+        scan_indices,
+        filtered_indices
+    })
+}
+
+/// Returns an A-Scan of a specific channel and position
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `x`: Column index
+/// * `y`: Row index
+/// * `range`: Optional `start` and `end` sample of the returned range and `max_points`,
+///   the maximum number of values per trace. Longer traces are reduced to the minimum
+///   and maximum of equally sized buckets. `unit` selects the unit of the samples,
+///   `normalized` (default), `fsh` (% full screen height), `counts` or `volts`.
+/// * `data_accessor`: Internal handler for the loaded data
+/// 
+/// # Returns
+/// If no error occurs, a JSON object will be returned containing the
+/// values of the A-Scan and the `start time` and `time resolution`.
+/// For downsampled traces the sample index of each value is added.
+/// 
+/// # Errors
+/// An error code will be returned if one the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * Any coordinate is invalid
+/// * The sample range is empty or `max_points` is less than `2`
+/// * Volts are requested, but no voltage range has been set for the channel
+#[get("/a_scan?<c>&<x>&<y>&<range..>")]
+fn get_a_scan(c: usize, x: usize, y: usize, range: AScanRange, data_accessor: &State<DataHandler>) -> Result<Json<AScanJson>, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let loaded_data = dataset.as_ref();
+
+            match loaded_data {
+                Some(data) => {
+                    let traces = a_scan_traces(data_accessor, data, c, x, y)?;
+                    a_scan_json(data_accessor, data, c, traces, range).map(Json)
+                }
+                None => {
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Dataset already used!")))
+        }
+    }
+}
+
+/// Returns the A-Scans of all channels at a position
+/// 
+/// # Arguments
+/// * `x`: Column index
+/// * `y`: Row index
+/// * `range`: Sample range, downsampling and unit, see `/a_scan`
+/// * `data_accessor`: Internal handler for the loaded data
+/// 
+/// # Returns
+/// A JSON list containing the A-Scan of each channel in the format of `/a_scan`
+/// 
+/// # Errors
+/// An error code will be returned if one the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * Any coordinate is invalid
+/// * The sample range is empty or `max_points` is less than `2`
+/// * Volts are requested, but no voltage range has been set for the channel
+#[get("/a_scan/channels?<x>&<y>&<range..>")]
+fn get_channel_a_scans(x: usize, y: usize, range: AScanRange, data_accessor: &State<DataHandler>) -> Result<Json<Vec<AScanJson>>, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            match dataset.as_ref() {
+                Some(data) => {
+                    (0..data.channel_count()).map(|c| {
+                        let traces = a_scan_traces(data_accessor, data, c, x, y)?;
+                        a_scan_json(data_accessor, data, c, traces, range)
+                    }).collect::<Result<Vec<AScanJson>, BadRequest<String>>>().map(Json)
+                }
+                None => {
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Dataset already used!")))
+        }
+    }
+}
+
+/// Returns the A-Scans of several positions of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `batch`: JSON object containing the `positions` as list of (column, row) indices
+///   (at most `MAX_BATCH_POSITIONS`), the optional sample range, downsampling and unit (`start`, `end`, `max_points`, `unit`) and
+///   `average` if the mean A-Scan of all positions should be added
+/// * `data_accessor`: Internal handler for the loaded data
+/// 
+/// # Returns
+/// A JSON object containing the A-Scan of each position in the format of `/a_scan`
+/// and the optional mean A-Scan
+/// 
+/// # Errors
+/// An error code will be returned if one the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * No position or more than `MAX_BATCH_POSITIONS` positions are given or any position is invalid
+/// * The sample range is empty or `max_points` is less than `2`
+/// * Volts are requested, but no voltage range has been set for the channel
+#[post("/a_scan/batch?<c>", data = "<batch>")]
+fn get_a_scan_batch(c: usize, batch: Json<AScanBatch>, data_accessor: &State<DataHandler>) -> Result<Json<AScanBatchJson>, BadRequest<String>> {
+    if batch.positions.is_empty() {
+        return Err(BadRequest(String::from("At least one position is required!")));
+    }
+
+    if batch.positions.len() > MAX_BATCH_POSITIONS {
+        return Err(BadRequest(format!("At most {} positions can be requested at once!", MAX_BATCH_POSITIONS)));
+    }
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            match dataset.as_ref() {
+                Some(data) => {
+                    let traces = batch.positions.iter()
+                        .map(|&(x, y)| a_scan_traces(data_accessor, data, c, x, y))
+                        .collect::<Result<Vec<(Vec<f64>, Vec<f64>)>, BadRequest<String>>>()?;
+
+                    let average = if batch.average {
+                        let count = traces.len() as f64;
+                        let samples = traces[0].0.len();
+                        let (mut raw_mean, mut filtered_mean) = (vec![0.0; samples], vec![0.0; samples]);
+
+                        for (raw, filtered) in &traces {
+                            raw_mean.iter_mut().zip(raw).for_each(|(mean, value)| *mean += value / count);
+                            filtered_mean.iter_mut().zip(filtered).for_each(|(mean, value)| *mean += value / count);
+                        }
+
+                        Some(a_scan_json(data_accessor, data, c, (raw_mean, filtered_mean), batch.range)?)
+                    }
+                    else {
+                        None
+                    };
+
+                    let a_scans = traces.into_iter()
+                        .map(|trace| a_scan_json(data_accessor, data, c, trace, batch.range))
+                        .collect::<Result<Vec<AScanJson>, BadRequest<String>>>()?;
+
+                    Ok(Json(AScanBatchJson { a_scans, average }))
+                }
+                None => {
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Dataset already used!")))
+        }
+    }
+}
+
+/// Returns the B-Scan along a row or column of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `axis`: `x` for a B-Scan along a row, `y` for a B-Scan along a column
+/// * `index`: Row (`axis=x`) or column (`axis=y`) index
+/// * `envelope`: The envelope of the filtered A-Scans should be returned (default: `false`)
+/// * `data_accessor`: Internal handler for the loaded data
+/// 
+/// # Returns
+/// JSON object containing the filtered A-Scans along the line, the position
+/// of each A-Scan in mm, the time axis and, if a material has been selected,
+/// the depth of each sample in mm
+/// 
+/// # Errors
+/// An error code will be returned if one the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded or the index is invalid
+#[get("/b_scan?<c>&<axis>&<index>&<envelope>")]
+fn get_b_scan(c: usize, axis: ScanAxis, index: usize, envelope: Option<bool>, data_accessor: &State<DataHandler>) -> Result<Json<BScanJson>, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            match dataset.as_ref() {
+                Some(data) => {
+                    let filter = data_accessor.filter(data, c);
+                    let volume = data_accessor.volume(data, c, &filter);
+
+                    let material = data_accessor.material()?;
+
+                    match section::b_scan(data, c, axis, index, Samples::of(&filter, volume.as_deref()), envelope.unwrap_or(false)) {
+                        Some(scan) => {
+                            let channel_subset = data.get_channel_subset(c).expect("Subset not found!");
+                            let resolution = match axis {
+                                ScanAxis::X => data.header.res_x,
+                                ScanAxis::Y => data.header.res_y
+                            };
+
+                            Ok(Json(BScanJson {
+                                position: (0..scan.nrows()).map(|position| position as f64 * resolution as f64).collect(),
+                                scan: scan.outer_iter().map(|row| row.to_vec()).collect(),
+                                time_start: channel_subset.min_sample_pos,
+                                time_step: channel_subset.sample_resolution,
+                                depth: sample_depths(channel_subset, 0, scan.ncols(), material.as_ref())
+                            }))
+                        }
+                        None => {
+                            Err(BadRequest(String::from("Invalid channel or index!")))
+                        }
+                    }
+                }
+                None => {
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Dataset already used!")))
+        }
+    }
+}
+
+/// Returns the B-Scan along an arbitrary polyline over the C-Scan
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `polyline`: JSON object with the `points` of the polyline as (column, row) indices,
+///   the `interpolation` (`nearest` or `bilinear`), the optional `step` in mm (at least
+///   `MIN_STEP_FRACTION` of the smallest resolution, at most `MAX_LINE_SAMPLES` A-Scans) and
+///   the `envelope` flag
+/// * `data_accessor`: Internal handler for the loaded data
+/// 
+/// # Returns
+/// JSON object containing the A-Scans along the polyline, the path length
+/// of each A-Scan in mm, the time axis and, if a material has been selected,
+/// the depth of each sample in mm
+/// 
+/// # Errors
+/// An error code will be returned if one the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The polyline is invalid
+/// * The channel hasn't been recorded
+#[post("/b_scan/line?<c>", data = "<polyline>")]
+fn get_line_b_scan(c: usize, polyline: Json<Polyline>, data_accessor: &State<DataHandler>) -> Result<Json<BScanJson>, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            match dataset.as_ref() {
+                Some(data) => {
+                    polyline.validate(data.header.samples_x.into(), data.header.samples_y.into(), data.header.res_x.into(), data.header.res_y.into())
+                        .map_err(BadRequest)?;
+
+                    let material = data_accessor.material()?;
+
+                    match section::line_b_scan(data, c, &polyline, &data_accessor.filter(data, c)) {
+                        Some((scan, position)) => {
+                            let channel_subset = data.get_channel_subset(c).expect("Subset not found!");
+
+                            Ok(Json(BScanJson {
+                                depth: sample_depths(channel_subset, 0, scan.ncols(), material.as_ref()),
+                                scan: scan.outer_iter().map(|row| row.to_vec()).collect(),
+                                position,
+                                time_start: channel_subset.min_sample_pos,
+                                time_step: channel_subset.sample_resolution
+                            }))
+                        }
+                        None => {
+                            Err(BadRequest(String::from("Channel not recorded!")))
+                        }
+                    }
+                }
+                None => {
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Dataset already used!")))
+        }
+    }
+}
+
+/// Returns the maximum amplitude projection of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `axis`: Projection direction; `x` results in a side view (row vs. time),
+///   `y` in an end view (column vs. time)
+/// * `start`: Optional first sample of the time gate
+/// * `end`: Optional first sample after the time gate
+/// * `roi`: Optional region of interest, see `/c_scan`. Only the datapoints inside contribute
+///   to the projection, positions without such datapoints are `null`.
+/// * `envelope`: The envelope should be used instead of the rectified A-Scans (default: `false`)
+/// * `data_accessor`: Internal handler for the loaded data
+/// 
+/// # Returns
+/// JSON object containing the projection image, the position of each row in mm,
+/// the time axis of the gate and, if a material has been selected, the depth
+/// of each sample in mm
+/// 
+/// # Errors
+/// An error code will be returned if one the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * The gate or the region of interest is invalid
+#[allow(clippy::too_many_arguments)]
+#[get("/projection?<c>&<axis>&<start>&<end>&<roi>&<envelope>")]
+fn get_projection(c: usize, axis: ScanAxis, start: Option<usize>, end: Option<usize>, roi: Option<Roi>, envelope: Option<bool>,
+    data_accessor: &State<DataHandler>) -> Result<Json<BScanJson>, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            match dataset.as_ref() {
+                Some(data) => {
+                    let (channel, channel_subset) = match (data.get_channel(c), data.get_channel_subset(c)) {
+                        (Some(channel), Some(subset)) => (channel, subset),
+                        _ => {
+                            return Err(BadRequest(String::from("Channel not recorded!")));
+                        }
+                    };
+
+                    let samples = channel.shape()[2];
+                    let roi = roi_mask(data, roi)?;
+
+                    let (start, end) = (start.unwrap_or(0), end.unwrap_or(samples));
+                    if start >= end || end > samples {
+                        return Err(BadRequest(String::from("Invalid gate!")));
+                    }
+
+                    let filter = data_accessor.filter(data, c);
+                    let volume = data_accessor.volume(data, c, &filter);
+                    let material = data_accessor.material()?;
+
+                    let mask = roi.as_ref().map(|roi| &roi.mask);
+
+                    match section::projection(data, c, axis, start, end, mask, Samples::of(&filter, volume.as_deref()), envelope.unwrap_or(false)) {
+                        Some((first, scan)) => {
+                            let resolution = match axis {
+                                ScanAxis::X => data.header.res_y,
+                                ScanAxis::Y => data.header.res_x
+                            };
+
+                            Ok(Json(BScanJson {
+                                position: (0..scan.nrows()).map(|position| (first + position) as f64 * resolution as f64).collect(),
+                                scan: scan.outer_iter().map(|row| row.to_vec()).collect(),
+                                time_start: channel_subset.sample_time(start as f64) as f32,
+                                time_step: channel_subset.sample_resolution,
+                                depth: sample_depths(channel_subset, start, scan.ncols(), material.as_ref())
+                            }))
+                        }
+                        None => {
+                            Err(BadRequest(String::from("Channel not recorded!")))
+                        }
+                    }
+                }
+                None => {
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Dataset already used!")))
+        }
+    }
+}
+
+/// Returns the header of a loaded dataset
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler of the dataset
+/// 
+/// # Returns
+/// If no error occurs the `Header` will be returned in JSON representation
+/// 
+/// # Errors
+/// An error code will be returned if one of the following issues occurs:
+/// * The dataset can't be locked
+/// * No data has been loaded
+#[get("/header")]
+fn get_data_header(data_accessor: &State<DataHandler>) -> Result<Json<data::Header>, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    Ok(Json(loaded_data.header.clone()))
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Get the C-Scan for a specific channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `start`: Start of the aperture
+/// * `end`: End of the aperture
+/// * `as_decibel`: `1` if the values should be returned in dB
+/// * `db_ref`: Reference (0 dB) of dB values, `raw` (single 16 bit step reduced by the gain, default),
+///   `fsh` (full screen height), `scan_max` (maximum of the scan) or `user`. Ignored if the channel
+///   is corrected by a DAC curve, the values are then relative to the curve.
+/// * `db_value`: Linear value of the amplitude measure used as reference for `db_ref=user`
+/// * `unit`: Unit of the amplitudes if they aren't returned in dB, `normalized` (default),
+///   `fsh` (% full screen height), `counts` or `volts`
+/// * `mode`: Amplitude measure inside the aperture (default: `peak`)
+/// * `iface`: Optional interface gate (`iface.start`, `iface.end`, `iface.threshold.value`,
+///   `iface.threshold.unit`). If provided, `start` and `end` are relative to the interface echo.
+/// * `gate_unit`: Unit of `start` and `end`, `samples` (default), `us` (time of the A-Scan axis
+///   or, with `iface`, time after the interface echo) or `mm` (depth, requires `velocity` or a selected material)
+/// * `velocity`: Sound velocity in m/s for `gate_unit=mm`, overrides the selected material
+/// * `roi`: Optional region of interest as JSON, e.g. `{"shape":"rect","x_start":0,"x_end":10,"y_start":0,"y_end":5}`
+///   or `{"shape":"polygon","points":[[0,0],[10,0],[5,5]],"unit":"mm"}`. Datapoints outside are `NaN`.
+/// * `format`: Response format selected by the `Accept` header
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// The JSON representation of the C-Scan values as a 2-D-Array or, if
+/// `application/octet-stream` is accepted, a `BinaryArray` with the axis scaling
+/// 
+/// # Errors
+/// An error code will be returned if one of the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * The aperture can't be converted or doesn't fit into the A-Scans
+/// * The region of interest is invalid
+/// * The dB reference or the unit is invalid
+#[allow(clippy::too_many_arguments)]
+#[get("/c_scan?<c>&<start>&<end>&<as_decibel>&<db_ref>&<db_value>&<unit>&<mode>&<iface>&<gate_unit>&<velocity>&<roi>")]
+fn get_c_scan(c: usize, start: f64, end: f64, as_decibel: usize, db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>,
+    unit: Option<AmplitudeUnit>, mode: Option<AmplitudeMode>, iface: Option<InterfaceGate>, gate_unit: Option<GateUnit>, velocity: Option<f64>,
+    roi: Option<Roi>, format: ResponseFormat, data_accessor: &State<DataHandler>) -> Result<ScanResponse, BadRequest<String>> {
+    let reference = data_accessor.decibel_reference(c, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let gate = get_gate(loaded_data, c, start, end, iface, gate_unit, data_accessor.depth_profile(velocity)?.as_ref())?;
+                    let mode = mode.unwrap_or_default();
+                    let roi = roi_mask(loaded_data, roi)?;
+                    let scale = unit_scale(data_accessor, c, unit)?;
+
+                    match cached_c_scan(data_accessor, loaded_data, c, &gate, mode, roi.as_ref()) {
+                        Some(c_scan) => { 
+                            let c_scan = if as_decibel == 1 {
+                                let gain = loaded_data.get_channel_subset(c).unwrap().gain;
+                                reference.convert(c_scan.as_ref(), mode, gain)
+                            }
+                            else {
+                                scale.convert(c_scan.as_ref(), mode)
+                            };
+
+                            Ok(scan_response(c_scan, loaded_data, format))
+                        }
+                        None => {
+                            println!("Failed to create c-scan");
+                            Err(BadRequest(String::from("C-Scan can't be created")))
+                        }
+                    }
+                }
+                None => {
+                    println!("No data loaded");
+                    Err(BadRequest(String::from("No data loaded!")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Data already used")))
+        }
+    }
+}
+
+/// Get a tile of the C-Scan pyramid of a specific channel
+/// 
+/// Level `0` contains the full resolution, each further level combines 2x2
+/// datapoints of the previous one. Tiles have a size of 256x256 datapoints.
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `start`: Start of the aperture
+/// * `end`: End of the aperture
+/// * `as_decibel`: `1` if the values should be returned in dB
+/// * `db_ref`: Reference of dB values, see `/c_scan`. `scan_max` refers to the complete C-Scan.
+/// * `db_value`: Reference value for `db_ref=user`
+/// * `unit`: Unit of the amplitudes if they aren't returned in dB, see `/c_scan`
+/// * `mode`: Amplitude measure inside the aperture (default: `peak`)
+/// * `iface`: Optional interface gate, see `/c_scan`
+/// * `gate_unit`: Unit of `start` and `end`, see `/c_scan`
+/// * `velocity`: Sound velocity in m/s for `gate_unit=mm`, overrides the selected material
+/// * `level`: Level of the pyramid
+/// * `tx`: Column index of the tile
+/// * `ty`: Row index of the tile
+/// * `aggregation`: Aggregation of the combined datapoints, `max` or `mean` (default: `max`)
+/// * `roi`: Optional region of interest, see `/c_scan`. Datapoints outside are `NaN`.
+/// * `format`: Response format selected by the `Accept` header
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the tile and its position or, if `application/octet-stream`
+/// is accepted, a `BinaryArray` with the position in its header
+/// 
+/// # Errors
+/// An error code will be returned if one of the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * The aperture can't be converted or doesn't fit into the A-Scans
+/// * The level or tile doesn't exist
+/// * The region of interest is invalid
+/// * The dB reference or the unit is invalid
+#[allow(clippy::too_many_arguments)]
+#[get("/c_scan/tile?<c>&<start>&<end>&<as_decibel>&<db_ref>&<db_value>&<unit>&<mode>&<iface>&<gate_unit>&<velocity>&<level>&<tx>&<ty>&<aggregation>&<roi>")]
+fn get_c_scan_tile(c: usize, start: f64, end: f64, as_decibel: usize, db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>,
+    unit: Option<AmplitudeUnit>, mode: Option<AmplitudeMode>, iface: Option<InterfaceGate>, gate_unit: Option<GateUnit>, velocity: Option<f64>,
+    level: usize, tx: usize, ty: usize, aggregation: Option<TileAggregation>, roi: Option<Roi>,
+    format: ResponseFormat, data_accessor: &State<DataHandler>) -> Result<TileResponse, BadRequest<String>> {
+    let reference = data_accessor.decibel_reference(c, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+    let decibel = (as_decibel == 1).then_some(reference);
+    let mode = mode.unwrap_or_default();
+    let aggregation = aggregation.unwrap_or_default();
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let gate = get_gate(loaded_data, c, start, end, iface, gate_unit, data_accessor.depth_profile(velocity)?.as_ref())?;
+                    let roi = roi_mask(loaded_data, roi)?;
+                    let c_scan = cached_c_scan(data_accessor, loaded_data, c, &gate, mode, roi.as_ref())
+                        .ok_or(BadRequest(String::from("C-Scan can't be created")))?;
+
+                    let (rows, cols) = c_scan.dim();
+                    let levels = tile::levels(rows, cols);
+
+                    if level >= levels {
+                        return Err(BadRequest(format!("Level {} doesn't exist, the pyramid has {} levels!", level, levels)));
+                    }
+
+                    let gain = loaded_data.get_channel_subset(c).unwrap().gain;
+                    let scale = unit_scale(data_accessor, c, unit)?;
+                    let unit = (as_decibel != 1).then_some(unit.unwrap_or_default());
+                    let voltage_range = unit.and(data_accessor.voltage_range(c)?);
+                    let filter = data_accessor.filter(loaded_data, c);
+                    let volume = data_accessor.volume(loaded_data, c, &filter);
+                    let samples = Samples::of(&filter, volume.as_deref());
+
+                    let level_scan = data_accessor.cached("c_scan_level", &(c, &gate, mode, samples.key(), decibel, unit, voltage_range, level, aggregation, roi.as_ref().map(|roi| &roi.roi)), || {
+                        let scan = if let Some(reference) = decibel {
+                            reference.convert(c_scan.as_ref(), mode, gain)
+                        }
+                        else {
+                            scale.convert(c_scan.as_ref(), mode)
+                        };
+
+                        Some(tile::downsample(&scan, 1 << level, aggregation))
+                    }).unwrap();
+
+                    let tile = tile::tile(&level_scan, tx, ty)
+                        .ok_or(BadRequest(format!("Tile ({}, {}) is outside of level {}!", tx, ty, level)))?;
+
+                    let factor = 1 << level;
+                    let info = TileInfo {
+                        level,
+                        levels,
+                        tx,
+                        ty,
+                        tiles_x: level_scan.ncols().div_ceil(tile::TILE_SIZE),
+                        tiles_y: level_scan.nrows().div_ceil(tile::TILE_SIZE),
+                        x_start: tile_start(tx, factor)?,
+                        y_start: tile_start(ty, factor)?,
+                        x_step: loaded_data.header.res_x * factor as f32,
+                        y_step: loaded_data.header.res_y * factor as f32,
+                        aggregation
+                    };
+
+                    match format {
+                        ResponseFormat::Json => Ok(TileResponse::Json(Json(TileJson {
+                            tile: vec_to_2d_list(&tile.iter().copied().collect::<Vec<f64>>(), tile.ncols()),
+                            info
+                        }))),
+                        ResponseFormat::Binary => Ok(TileResponse::Binary(BinaryArray {
+                            shape: tile.shape().to_vec(),
+                            data: tile.iter().map(|value| *value as f32).collect(),
+                            info
+                        }))
+                    }
+                }
+                None => {
+                    println!("No data loaded");
+                    Err(BadRequest(String::from("No data loaded!")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Data already used")))
+        }
+    }
+}
+
+/// Get C-Scans of consecutive time windows for a specific channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `as_decibel`: `1` if the values should be returned in dB
+/// * `db_ref`: Reference of dB values, see `/c_scan`. `scan_max` refers to the complete stack.
+/// * `db_value`: Reference value for `db_ref=user`
+/// * `unit`: Unit of the amplitudes if they aren't returned in dB, see `/c_scan`
+/// * `mode`: Amplitude measure inside each window (default: `peak`)
+/// * `slices`: Time windows given by `start`, `end`, `width` and `step` in samples, at most `MAX_TIME_SLICES`
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A `BinaryArray` of shape `[windows, rows, columns]` containing the start time of
+/// each window, the window width and the axis scaling in its header
+/// 
+/// # Errors
+/// An error code will be returned if one of the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * The time windows are invalid or too many
+/// * The dB reference or the unit is invalid
+#[allow(clippy::too_many_arguments)]
+#[get("/c_scan/stack?<c>&<as_decibel>&<db_ref>&<db_value>&<unit>&<mode>&<slices..>")]
+fn get_c_scan_stack(c: usize, as_decibel: usize, db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>, unit: Option<AmplitudeUnit>,
+    mode: Option<AmplitudeMode>, slices: TimeSlices, data_accessor: &State<DataHandler>) -> Result<BinaryArray<CScanStackInfo>, BadRequest<String>> {
+    let mode = mode.unwrap_or_default();
+    let reference = data_accessor.decibel_reference(c, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let (channel, subset) = match (loaded_data.get_channel(c), loaded_data.get_channel_subset(c)) {
+                        (Some(channel), Some(subset)) => (channel, subset),
+                        _ => {
+                            return Err(BadRequest(String::from("Channel not recorded!")));
+                        }
+                    };
+
+                    slices.validate(channel.shape()[2]).map_err(BadRequest)?;
+                    let scale = unit_scale(data_accessor, c, unit)?;
+
+                    let filter = data_accessor.filter(loaded_data, c);
+                    let volume = data_accessor.volume(loaded_data, c, &filter);
+
+                    match loaded_data.c_scan_stack(c, &slices, Samples::of(&filter, volume.as_deref()), (as_decibel == 1).then_some(reference), mode) {
+                        Some(stack) => {
+                            let stack = if as_decibel == 1 {
+                                stack
+                            }
+                            else {
+                                stack.mapv(|value| scale.amplitude(value as f64, mode) as f32)
+                            };
+
+                            let info = CScanStackInfo {
+                                window_start: slices.windows().iter().map(|(start, _)| subset.sample_time(*start as f64)).collect(),
+                                window_width: subset.sample_time(slices.width as f64) - subset.sample_time(0.0),
+                                x_step: loaded_data.header.res_x,
+                                y_step: loaded_data.header.res_y,
+                                mode
+                            };
+
+                            Ok(BinaryArray { shape: stack.shape().to_vec(), data: stack.into_raw_vec_and_offset().0, info })
+                        }
+                        None => {
+                            Err(BadRequest(String::from("C-Scan stack can't be created")))
+                        }
+                    }
+                }
+                None => {
+                    println!("No data loaded");
+                    Err(BadRequest(String::from("No data loaded!")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Data already used")))
+        }
+    }
+}
+
+/// Get the D-Scan for a specific channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `start`: Start of the aperture
+/// * `end`: End of the aperture
+/// * `method`: Time of flight detection method (default: `peak`)
+/// * `threshold`: Threshold for the threshold based methods (default: `50`)
+/// * `threshold_unit`: Unit of the threshold, `percent` or `db` (default: `percent`)
+/// * `threshold_ref`: Reference of a dB threshold, `raw`, `fsh` or `user` (default: `raw`), see `/c_scan`
+/// * `threshold_ref_value`: Reference value for `threshold_ref=user`
+/// * `iface`: Optional interface gate. If provided, `start` and `end` are relative
+///   to the interface echo.
+/// * `gate_unit`: Unit of `start` and `end`, `samples` (default), `us` (time of the A-Scan axis
+///   or, with `iface`, time after the interface echo) or `mm` (depth, requires `velocity` or a selected material)
+/// * `velocity`: Sound velocity in m/s for `gate_unit=mm` and `depth`, overrides the selected material
+/// * `depth`: The D-Scan should be returned as depth in mm (default: `false`)
+/// * `roi`: Optional region of interest as JSON, see `/c_scan`
+/// * `format`: Response format selected by the `Accept` header
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON representation of the D-Scan times in µs (or depths in mm) as a 2-D-Array.
+/// Datapoints without a detected echo or outside of the region of interest are `null`.
+/// If `application/octet-stream` is accepted, a `BinaryArray` is returned instead
+/// with `NaN` for these datapoints.
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * The aperture can't be converted or doesn't fit into the A-Scans
+/// * The region of interest is invalid
+/// * The depth is requested without a velocity or a selected material
+#[allow(clippy::too_many_arguments)]
+#[get("/d_scan?<c>&<start>&<end>&<method>&<threshold>&<threshold_unit>&<threshold_ref>&<threshold_ref_value>&<iface>&<gate_unit>&<velocity>&<depth>&<roi>")]
+fn get_d_scan(c: usize, start: f64, end: f64, method: Option<TofMethod>, threshold: Option<f64>, threshold_unit: Option<ThresholdUnit>,
+    threshold_ref: Option<DecibelReferenceKind>, threshold_ref_value: Option<f64>,
+    iface: Option<InterfaceGate>, gate_unit: Option<GateUnit>, velocity: Option<f64>, depth: Option<bool>, roi: Option<Roi>, format: ResponseFormat,
+    data_accessor: &State<DataHandler>) -> Result<ScanResponse, BadRequest<String>> {
+    let threshold = get_threshold(threshold, threshold_unit, threshold_ref, threshold_ref_value)?;
+    let depth = data_accessor.depth_scan_profile(depth, velocity)?;
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+            
+            match us_data {
+                Some(loaded_data) => {
+                    let gate = get_gate(loaded_data, c, start, end, iface, gate_unit, data_accessor.depth_profile(velocity)?.as_ref())?;
+                    let roi = roi_mask(loaded_data, roi)?;
+
+                    match cached_d_scan(data_accessor, loaded_data, c, &gate, method.unwrap_or_default(), threshold, roi.as_ref()) {
+                        Some(d_scan) => {
+                            match depth {
+                                Some(profile) => Ok(scan_response(depth_scan(d_scan.as_ref(), &profile), loaded_data, format)),
+                                None => Ok(scan_response(d_scan.as_ref().clone(), loaded_data, format))
+                            }
+                        }
+                        None => {
+                            Err(BadRequest(String::from("Failed to generate D-Scan")))
+                        }
+                    }
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Set the named gates of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `config`: Gate configuration as JSON
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The gate configuration is inconsistent
+/// * The gate configurations can't be locked
+#[post("/gates?<c>", data = "<config>")]
+fn set_gates(c: usize, config: Json<GateConfig>, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    let config = config.into_inner();
+    config.validate().map_err(BadRequest)?;
+
+    match data_accessor.gates.lock() {
+        Ok(mut gates) => {
+            gates.insert(c, config);
+            Ok("gates updated")
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock gates")))
+        }
+    }
+}
+
+/// Get the named gates of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// The gate configuration of the channel. If no gates have been set
+/// the configuration is empty.
+/// 
+/// # Errors
+/// An error code is returned if the gate configurations can't be locked
+#[get("/gates?<c>")]
+fn get_gates(c: usize, data_accessor: &State<DataHandler>) -> Result<Json<GateConfig>, BadRequest<String>> {
+    data_accessor.gate_config(c).map(Json)
+}
+
+/// Get the C- and D-Scans of all named gates of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `as_decibel`: `1` if the amplitudes should be returned in dB
+/// * `db_ref`: Reference of dB values, see `/c_scan`. `scan_max` refers to the C-Scan of each gate.
+/// * `db_value`: Reference value for `db_ref=user`
+/// * `unit`: Unit of the amplitudes if they aren't returned in dB, see `/c_scan`
+/// * `depth`: The D-Scans should be returned as depth in mm (default: `false`)
+/// * `velocity`: Sound velocity in m/s for `depth`, overrides the selected material
+/// * `roi`: Optional region of interest as JSON, see `/c_scan`
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the C- and D-Scan of each gate and the
+/// configured time differences between the gates
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset or the gates can't be locked
+/// * No data is loaded
+/// * No gates have been set for the channel
+/// * The channel hasn't been recorded
+/// * The region of interest is invalid
+/// * The dB reference or the unit is invalid
+/// * The depth is requested without a velocity or a selected material
+#[allow(clippy::too_many_arguments)]
+#[get("/gate_scans?<c>&<as_decibel>&<db_ref>&<db_value>&<unit>&<depth>&<velocity>&<roi>")]
+fn get_gate_scans(c: usize, as_decibel: usize, db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>, unit: Option<AmplitudeUnit>,
+    depth: Option<bool>, velocity: Option<f64>, roi: Option<Roi>, data_accessor: &State<DataHandler>) -> Result<Json<GateScansJson>, BadRequest<String>> {
+    let reference = data_accessor.decibel_reference(c, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+    let depth = data_accessor.depth_scan_profile(depth, velocity)?;
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let config = data_accessor.gate_config(c)?;
+
+                    if config.gates.is_empty() {
+                        return Err(BadRequest(String::from("No gates defined for this channel!")));
+                    }
+
+                    let cols = loaded_data.header.samples_x.into();
+                    let roi = roi_mask(loaded_data, roi)?;
+                    let scale = unit_scale(data_accessor, c, unit)?;
+
+                    match cached_gate_scans(data_accessor, loaded_data, c, &config, roi.as_ref()) {
+                        Some(scans) => {
+                            let differences = gate_differences(&config, &scans).into_iter()
+                                .map(|(name, scan)| GateDifferenceJson { name, scan: vec_to_2d_list(&scan.into_raw_vec_and_offset().0, cols) })
+                                .collect();
+
+                            let gain = loaded_data.get_channel_subset(c).unwrap().gain;
+
+                            let gates = config.gates.iter().zip(scans.iter())
+                                .map(|(gate, scan)| {
+                                    let c_scan = if as_decibel == 1 {
+                                        reference.convert(&scan.amplitude, gate.mode, gain)
+                                    }
+                                    else {
+                                        scale.convert(&scan.amplitude, gate.mode)
+                                    };
+
+                                    let d_scan = match &depth {
+                                        Some(profile) => depth_scan(&scan.time, profile),
+                                        None => scan.time.clone()
+                                    };
+
+                                    GateScanJson {
+                                        name: gate.name.clone(),
+                                        c_scan: vec_to_2d_list(&c_scan.into_raw_vec_and_offset().0, cols),
+                                        d_scan: vec_to_2d_list(&d_scan.into_raw_vec_and_offset().0, cols)
+                                    }
+                                })
+                                .collect();
+
+                            Ok(Json(GateScansJson { gates, differences }))
+                        }
+                        None => {
+                            Err(BadRequest(String::from("Failed to generate gate scans")))
+                        }
+                    }
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Get the wall thickness map of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `config`: Thickness configuration (`velocity` in m/s, name of the backwall
+///   `gate` and optional `reference` gate for interface or echo-to-echo measurements)
+/// * `roi`: Optional region of interest as JSON, see `/c_scan`. Only the datapoints
+///   inside the region are evaluated and included in the statistics.
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the wall thickness in mm as a 2-D-Array and its statistics
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset or the gates can't be locked
+/// * No data is loaded
+/// * The velocity isn't positive or a gate hasn't been defined
+/// * The channel hasn't been recorded
+/// * The region of interest is invalid
+#[get("/thickness?<c>&<roi>&<config..>")]
+fn get_thickness(c: usize, roi: Option<Roi>, config: ThicknessConfig, data_accessor: &State<DataHandler>) -> Result<Json<ThicknessJson>, BadRequest<String>> {
+    let gate_config = data_accessor.gate_config(c)?;
+    config.validate(&gate_config).map_err(BadRequest)?;
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let roi = roi_mask(loaded_data, roi)?;
+
+                    match cached_gate_scans(data_accessor, loaded_data, c, &gate_config, roi.as_ref()) {
+                        Some(scans) => {
+                            let thickness = config.thickness(&gate_config, &scans);
+                            let statistics = Statistics::of(&thickness);
+
+                            Ok(Json(ThicknessJson {
+                                thickness: vec_to_2d_list(&thickness.into_raw_vec_and_offset().0, loaded_data.header.samples_x.into()),
+                                statistics
+                            }))
+                        }
+                        None => {
+                            Err(BadRequest(String::from("Failed to generate thickness map")))
+                        }
+                    }
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Get the statistics and the histogram of a scan of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `scan`: Evaluated scan, `c_scan`, `d_scan` or `thickness`
+/// * `start`: Start of the aperture, required for C- and D-Scans
+/// * `end`: End of the aperture, required for C- and D-Scans
+/// * `as_decibel`: `1` if the C-Scan should be evaluated in dB
+/// * `db_ref`: Reference of dB values, see `/c_scan`
+/// * `db_value`: Reference value for `db_ref=user`
+/// * `unit`: Unit of the C-Scan if it isn't evaluated in dB, see `/c_scan`
+/// * `mode`: Amplitude measure of the C-Scan (default: `peak`)
+/// * `method`: Time of flight detection method of the D-Scan (default: `peak`)
+/// * `threshold`: Threshold for the threshold based methods (default: `50`)
+/// * `threshold_unit`: Unit of the threshold (default: `percent`)
+/// * `threshold_ref`: Reference of a dB threshold, see `/d_scan`
+/// * `threshold_ref_value`: Reference value for `threshold_ref=user`
+/// * `iface`: Optional interface gate, see `/c_scan`
+/// * `gate_unit`: Unit of `start` and `end`, see `/c_scan`
+/// * `velocity`: Sound velocity in m/s for `gate_unit=mm`, overrides the selected material
+/// * `thickness`: Wall thickness configuration (`thickness.velocity`, `thickness.gate`,
+///   `thickness.reference`), required for the thickness map
+/// * `roi`: Optional region of interest as JSON, see `/c_scan`
+/// * `bins`: Number of histogram bins between `1` and `1024` (default: `64`)
+/// * `percentiles`: Requested percentiles between `0` and `100`, may be repeated
+///   (default: `5`, `25`, `75` and `95`)
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the unit, the statistics and the histogram of all valid
+/// datapoints. Datapoints without a value, e.g. without a detected echo, are ignored.
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset or the gates can't be locked
+/// * No data is loaded
+/// * The aperture or thickness configuration of the scan is missing or invalid
+/// * The number of bins or a percentile is out of range
+/// * The region of interest, the dB reference or the unit is invalid
+/// * The channel hasn't been recorded
+/// * The aperture can't be converted or doesn't fit into the A-Scans
+#[allow(clippy::too_many_arguments)]
+#[get("/stats?<c>&<scan>&<start>&<end>&<as_decibel>&<db_ref>&<db_value>&<unit>&<mode>&<method>&<threshold>&<threshold_unit>&<threshold_ref>&<threshold_ref_value>&<iface>&<gate_unit>&<velocity>&<thickness>&<roi>&<bins>&<percentiles>")]
+fn get_statistics(c: usize, scan: StatisticsSource, start: Option<f64>, end: Option<f64>, as_decibel: Option<usize>,
+    db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>, unit: Option<AmplitudeUnit>, mode: Option<AmplitudeMode>, method: Option<TofMethod>,
+    threshold: Option<f64>, threshold_unit: Option<ThresholdUnit>, threshold_ref: Option<DecibelReferenceKind>, threshold_ref_value: Option<f64>,
+    iface: Option<InterfaceGate>, gate_unit: Option<GateUnit>, velocity: Option<f64>,
+    thickness: Option<ThicknessConfig>, roi: Option<Roi>, bins: Option<usize>, percentiles: Vec<f64>,
+    data_accessor: &State<DataHandler>) -> Result<Json<StatisticsJson>, BadRequest<String>> {
+    let bins = bins.unwrap_or(DEFAULT_BINS);
+    let reference = data_accessor.decibel_reference(c, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+    let percentiles = if percentiles.is_empty() { DEFAULT_PERCENTILES.to_vec() } else { percentiles };
+
+    if !(1..=1024).contains(&bins) {
+        return Err(BadRequest(String::from("The number of bins has to be between 1 and 1024!")));
+    }
+
+    if percentiles.iter().any(|percentile| !(0.0..=100.0).contains(percentile)) {
+        return Err(BadRequest(String::from("Percentiles have to be between 0 and 100!")));
+    }
+
+    let aperture = match (scan, start, end) {
+        (StatisticsSource::Thickness, _, _) => None,
+        (_, Some(start), Some(end)) => Some((start, end)),
+        _ => return Err(BadRequest(String::from("The aperture (start, end) is required for C- and D-Scans!")))
+    };
+
+    let gate_config = data_accessor.gate_config(c)?;
+    let profile = data_accessor.depth_profile(velocity)?;
+
+    if scan == StatisticsSource::Thickness {
+        match &thickness {
+            Some(config) => config.validate(&gate_config).map_err(BadRequest)?,
+            None => return Err(BadRequest(String::from("The thickness configuration is required for the thickness map!")))
+        }
+    }
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let roi = roi_mask(loaded_data, roi)?;
+                    let subset = loaded_data.get_channel_subset(c).ok_or(BadRequest(String::from("The channel hasn't been recorded!")))?;
+                    let scale = unit_scale(data_accessor, c, unit)?;
+                    let gate = aperture.map(|(start, end)| get_gate(loaded_data, c, start, end, iface, gate_unit, profile.as_ref())).transpose()?;
+
+                    let (values, unit) = match (scan, gate, thickness) {
+                        (StatisticsSource::CScan, Some(gate), _) => {
+                            let mode = mode.unwrap_or_default();
+                            let c_scan = cached_c_scan(data_accessor, loaded_data, c, &gate, mode, roi.as_ref());
+
+                            match as_decibel {
+                                Some(1) => (c_scan.map(|c_scan| reference.convert(c_scan.as_ref(), mode, subset.gain)), "dB"),
+                                _ => (c_scan.map(|c_scan| scale.convert(c_scan.as_ref(), mode)), unit.unwrap_or_default().symbol())
+                            }
+                        }
+                        (StatisticsSource::DScan, Some(gate), _) => {
+                            let threshold = get_threshold(threshold, threshold_unit, threshold_ref, threshold_ref_value)?;
+                            (cached_d_scan(data_accessor, loaded_data, c, &gate, method.unwrap_or_default(), threshold, roi.as_ref())
+                                .map(|d_scan| d_scan.as_ref().clone()), "µs")
+                        }
+                        (_, _, Some(config)) => {
+                            (cached_gate_scans(data_accessor, loaded_data, c, &gate_config, roi.as_ref())
+                                .map(|scans| config.thickness(&gate_config, &scans)), "mm")
+                        }
+                        _ => (None, "")
+                    };
+
+                    match values {
+                        Some(values) => Ok(Json(StatisticsJson { unit, scan: ScanStatistics::of(&values, &percentiles, bins) })),
+                        None => Err(BadRequest(String::from("Failed to generate the scan")))
+                    }
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Detect and size the indications of the C-Scan of a channel
+/// 
+/// The C-Scan is converted into dB and segmented with the threshold.
+/// Connected datapoints (including diagonal neighbours) form an indication.
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `start`: Start of the aperture
+/// * `end`: End of the aperture
+/// * `mode`: Amplitude measure inside the aperture (default: `peak`)
+/// * `db_ref`: Reference of the dB values, see `/c_scan`
+/// * `db_value`: Reference value for `db_ref=user`
+/// * `iface`: Optional interface gate, see `/c_scan`
+/// * `gate_unit`: Unit of `start` and `end`, see `/c_scan`
+/// * `velocity`: Sound velocity in m/s for `gate_unit=mm`, overrides the selected material
+/// * `roi`: Optional region of interest as JSON, see `/c_scan`
+/// * `config`: Defect detection (`threshold` in dB, `level` `absolute` or `drop`,
+///   optional `reference` level in dB and `min_area` in mm²)
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the absolute threshold in dB and the list of indications
+/// with their area, bounding box, centroid, peak and -6 dB size in mm
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset can't be locked
+/// * No data is loaded
+/// * The configuration, the dB reference or the region of interest is invalid
+/// * The channel hasn't been recorded
+/// * The aperture can't be converted or doesn't fit into the A-Scans
+#[allow(clippy::too_many_arguments)]
+#[get("/defects?<c>&<start>&<end>&<mode>&<db_ref>&<db_value>&<iface>&<gate_unit>&<velocity>&<roi>&<config..>")]
+fn get_defects(c: usize, start: f64, end: f64, mode: Option<AmplitudeMode>, db_ref: Option<DecibelReferenceKind>, db_value: Option<f64>,
+    iface: Option<InterfaceGate>, gate_unit: Option<GateUnit>, velocity: Option<f64>, roi: Option<Roi>, config: DefectConfig,
+    data_accessor: &State<DataHandler>) -> Result<Json<DefectsJson>, BadRequest<String>> {
+    config.validate().map_err(BadRequest)?;
+    let reference = data_accessor.decibel_reference(c, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+
+    let mode = mode.unwrap_or_default();
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let gate = get_gate(loaded_data, c, start, end, iface, gate_unit, data_accessor.depth_profile(velocity)?.as_ref())?;
+                    let roi = roi_mask(loaded_data, roi)?;
+
+                    match (cached_c_scan(data_accessor, loaded_data, c, &gate, mode, roi.as_ref()), loaded_data.get_channel_subset(c)) {
+                        (Some(c_scan), Some(subset)) => {
+                            let c_scan_db = reference.convert(c_scan.as_ref(), mode, subset.gain);
+                            Ok(Json(detect_defects(&config, &c_scan_db, loaded_data)))
+                        }
+                        _ => {
+                            Err(BadRequest(String::from("C-Scan can't be created")))
+                        }
+                    }
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Evaluate an acceptance profile on a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `profile`: JSON acceptance profile with the optional rule groups `indications`
+///   (aperture `gate`, `mode`, segmentation as in `/defects`, `max_size`, `max_area`
+///   and `zones` with `name`, `roi` and `max_area`), `thickness` (thickness configuration
+///   and `min`) and `backwall` (named `gate`, optional `reference` and `max_loss`). All
+///   dB values refer to `db_reference` (`"raw"` by default, `"fsh"`, `"scan_max"` or `{"user": value}`)
+///   or to the DAC curve of the channel.
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the pass/fail verdict, the segmentation threshold and
+/// each violated rule with its limit, the worst value and the violating indications.
+/// Datapoints without a detected echo violate the thickness and back-wall rules,
+/// their area is given by `missing_area`.
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset or the gates can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+/// * The profile is invalid or doesn't fit to the named gates or the A-Scans
+#[post("/acceptance?<c>", data = "<profile>")]
+fn evaluate_acceptance(c: usize, profile: Json<AcceptanceProfile>, data_accessor: &State<DataHandler>) -> Result<Json<AcceptanceResult>, BadRequest<String>> {
+    let gate_config = data_accessor.gate_config(c)?;
+    let reference = data_accessor.decibel_reference(c, profile.db_reference)?;
+
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            let us_data = dataset.as_ref();
+
+            match us_data {
+                Some(loaded_data) => {
+                    let (subset, samples) = match (loaded_data.get_channel_subset(c), loaded_data.get_channel(c)) {
+                        (Some(subset), Some(samples)) => (subset, samples.shape()[2]),
+                        _ => return Err(BadRequest(String::from("The channel hasn't been recorded!")))
+                    };
+                    profile.validate(&gate_config, samples).map_err(BadRequest)?;
+
+                    let c_scan_db = match &profile.indications {
+                        Some(rules) => {
+                            let c_scan = cached_c_scan(data_accessor, loaded_data, c, &rules.gate, rules.mode, None)
+                                .ok_or(BadRequest(String::from("C-Scan can't be created")))?;
+                            Some(reference.convert(c_scan.as_ref(), rules.mode, subset.gain))
+                        }
+                        None => None
+                    };
+
+                    let gate_scans = if profile.thickness.is_some() || profile.backwall.is_some() {
+                        Some(cached_gate_scans(data_accessor, loaded_data, c, &gate_config, None)
+                            .ok_or(BadRequest(String::from("Failed to generate the gate scans")))?)
+                    }
+                    else {
+                        None
+                    };
+
+                    let thickness = profile.thickness.as_ref().zip(gate_scans.as_ref())
+                        .map(|(rule, scans)| rule.config.thickness(&gate_config, scans));
+                    let backwall_db = profile.backwall.as_ref().zip(gate_scans.as_ref()).map(|(rule, scans)| {
+                        let index = gate_config.index_of(&rule.gate).unwrap();
+                        reference.convert(&scans[index].amplitude, gate_config.gates[index].mode, subset.gain)
+                    });
+
+                    let scans = AcceptanceScans {
+                        c_scan_db: c_scan_db.as_ref(),
+                        thickness: thickness.as_ref(),
+                        backwall_db: backwall_db.as_ref(),
+                        res_x: loaded_data.header.res_x.into(),
+                        res_y: loaded_data.header.res_y.into()
+                    };
+
+                    profile.evaluate(&scans).map(Json).map_err(BadRequest)
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Start the precomputation of the filtered volume of a channel
+/// 
+/// The filtered A-Scans are computed in the background and used by all
+/// scans of the channel as soon as they are ready. The precomputation is
+/// repeated after loading a new dataset or changing the filter.
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `envelope`: The envelope should be precomputed as well (default: `false`)
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The dataset or the volumes can't be locked
+/// * No data is loaded
+/// * The channel hasn't been recorded
+#[post("/volume?<c>&<envelope>")]
+fn start_volume(c: usize, envelope: Option<bool>, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    let ds = data_accessor.dataset.lock();
+
+    match ds {
+        Ok(dataset) => {
+            match dataset.as_ref().map(|loaded_data| (loaded_data, loaded_data.get_channel(c))) {
+                Some((loaded_data, Some(channel))) => {
+                    match data_accessor.volumes.lock() {
+                        Ok(mut store) => {
+                            store.start(&data_accessor.volumes, c, channel.clone(), data_accessor.filter(loaded_data, c), envelope.unwrap_or(false));
+                            Ok("precomputation started")
+                        }
+                        Err(error) => {
+                            println!("{}", error);
+                            Err(BadRequest(String::from("Failed to lock volumes")))
+                        }
+                    }
+                }
+                Some((_, None)) => {
+                    Err(BadRequest(String::from("Channel not recorded!")))
+                }
+                None => {
+                    println!("No data loaded!");
+                    Err(BadRequest(String::from("No data loaded")))
+                }
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock dataset")))
+        }
+    }
+}
+
+/// Get the status of the filtered volume of a channel
+/// 
+/// The status is only read, a stale volume is recomputed by the next scan request.
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// JSON object containing the state (`none`, `running`, `ready` or `stale`)
+/// and the progress of a running precomputation
+/// 
+/// # Errors
+/// An error code is returned if the dataset or the volumes can't be locked
+#[get("/volume?<c>")]
+fn get_volume_status(c: usize, data_accessor: &State<DataHandler>) -> Result<Json<VolumeStatus>, BadRequest<String>> {
+    let filter = match data_accessor.dataset.lock() {
+        Ok(dataset) => {
+            match dataset.as_ref() {
+                Some(loaded_data) => data_accessor.filter(loaded_data, c),
+                None => AScanFilter::load()
+            }
+        }
+        Err(error) => {
+            println!("{}", error);
+            return Err(BadRequest(String::from("Failed to lock dataset")));
+        }
+    };
+
+    match data_accessor.volumes.lock() {
+        Ok(store) => Ok(Json(store.status(c, &filter.key()))),
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock volumes")))
+        }
+    }
+}
+
+/// Remove the filtered volume of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if the volumes can't be locked
+#[delete("/volume?<c>")]
+fn remove_volume(c: usize, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    match data_accessor.volumes.lock() {
+        Ok(mut store) => {
+            store.remove(c);
+            Ok("volume removed")
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock volumes")))
+        }
+    }
+}
+
+/// Set the distance amplitude correction of a channel
+/// 
+/// The curve is applied to all scans and A-Scans of the channel. A TCG curve adds
+/// its gain to the samples. A DAC curve scales the reference reflectors to full screen
+/// height, all dB values of the channel are then relative to the curve regardless of `db_ref`.
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `curve`: JSON object with the `kind` (`tcg` or `dac`) and the `points` of the
+///   curve, each with the `time` in µs and the `level` in dB. The level of a DAC curve is the
+///   amplitude of the reference reflector relative to full screen height of the filtered A-Scan.
+/// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// A `String` containing `start + data * scale` for all values in CSV format
-fn array_to_csv<T>(array: ArrayBase<OwnedRepr<T>, Dim<[usize; 2]>>, start: f64, scale: f64) -> String 
-    where T: Clone + Copy + Display + Into<f64> {
-    let mut output = String::new();
-    for row in array.outer_iter() {
-        let values = row.to_vec();
-        let first_value = format!("{}", values[0].into() * scale + start);
-        output = output.add(&first_value);
-        for value in values {
-            output = output.add(",").add(&format!("{}", value.into() * scale + start));
-        }
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The curve has no points, a value isn't finite or the times aren't increasing
+/// * The correction curves can't be locked
+#[post("/correction?<c>", data = "<curve>")]
+fn set_correction(c: usize, curve: Json<CorrectionCurve>, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    let curve = curve.into_inner();
+    curve.validate().map_err(BadRequest)?;
 
-        output = output.add("\n");
+    match data_accessor.corrections.lock() {
+        Ok(mut corrections) => {
+            corrections.insert(c, curve);
+            Ok("correction updated")
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock correction curves")))
+        }
     }
-
-    output
 }
 
-/// Read a CSV file and return the data as a 2D vector
+/// Get the distance amplitude correction of a channel
 /// 
 /// # Arguments
-/// * `csv`: CSV file as string
+/// * `c`: Channel index
+/// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// If the data has been parsed correctly a 2D vector will be returned.
+/// The correction curve of the channel or `null` if no curve has been set
 /// 
-/// # Failures
-/// If a value can't be mapped into a Float variable `None` will be returned.
-fn csv_to_array(csv: &String) -> Option<Vec<Vec<f32>>> {
-    let mut array = vec![];
-
-    for line in csv.lines() {
-        let mut new_row = vec![];
+/// # Errors
+/// An error code is returned if the correction curves can't be locked
+#[get("/correction?<c>")]
+fn get_correction(c: usize, data_accessor: &State<DataHandler>) -> Result<Json<Option<CorrectionCurve>>, BadRequest<String>> {
+    data_accessor.correction(c).map(Json)
+}
 
-        for element in line.split(',') {
-            match element.parse() {
-                Ok(number) => {
-                    new_row.push(number);
-                }
-                Err(_) => {
-                    return None;
-                }
-            }
+/// Remove the distance amplitude correction of a channel
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if the correction curves can't be locked
+#[delete("/correction?<c>")]
+fn remove_correction(c: usize, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    match data_accessor.corrections.lock() {
+        Ok(mut corrections) => {
+            corrections.remove(&c);
+            Ok("correction removed")
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock correction curves")))
         }
-
-        array.push(new_row);
     }
-
-    Some(array)
 }
 
-/// Converts a Vector into a List of Lists
+/// Set the software gain of a channel
+/// 
+/// The gain is applied to the filtered samples after the distance amplitude
+/// correction and affects all scans, filtered A-Scans and exports of the channel.
 /// 
 /// # Arguments
-/// * `vector`: Vector with data
-/// * `cols`: Max Length for the inner lists
+/// * `c`: Channel index
+/// * `gain`: Gain in dB, `0` removes the gain
+/// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// A List of Lists with `cols` values
-fn vec_to_2d_list<T>(vector: &Vec<T>, cols: usize) -> Vec<Vec<T>>
-    where T: Clone {
-    let mut scan = vec![];
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The gain isn't finite
+/// * The software gains can't be locked
+#[post("/software_gain?<c>&<gain>")]
+fn set_software_gain(c: usize, gain: f64, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    if !gain.is_finite() {
+        return Err(BadRequest(String::from("The gain has to be finite!")));
+    }
 
-    for (index, value) in vector.iter().enumerate() {
-        let row = index / cols;
+    match data_accessor.software_gains.lock() {
+        Ok(mut gains) => {
+            if gain == 0.0 {
+                gains.remove(&c);
+            }
+            else {
+                gains.insert(c, gain);
+            }
 
-        if row >= scan.len() {
-            scan.push(vec![]);
+            Ok("software gain updated")
+        }
+        Err(error) => {
+            println!("{}", error);
+            Err(BadRequest(String::from("Failed to lock software gains")))
         }
-
-        let current_row_list = scan.last_mut().unwrap();
-        current_row_list.push(value.clone());
     }
-
-    scan
 }
 
-/// Returns an A-Scan of a specific channel and position
+/// Get the software gain of a channel
 /// 
 /// # Arguments
 /// * `c`: Channel index
-/// * `x`: Column index
-/// * `y`: Row index
-/// * `data_accessor`: Internal handler for the loaded data
+/// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// If no error occurs, a JSON object will be returned containing the
-/// values of the A-Scan and the `start time` and `time resolution`
+/// The gain in dB, `0` if no gain has been set
 /// 
 /// # Errors
-/// An error code will be returned if one the following issues occurs:
-/// * The dataset can't be locked
-/// * No data is loaded
-/// * The channel hasn't been recorded
-/// * Any coordinate is invalid
-#[get("/a_scan?<c>&<x>&<y>")]
-fn get_a_scan(c: usize, x: usize, y: usize, data_accessor: &State<DataHandler>) -> Result<Json<AScanJson>, BadRequest<String>> {
-    let ds = data_accessor.dataset.lock();
-
-    match ds {
-        Ok(dataset) => {
-            let loaded_data = dataset.as_ref();
+/// An error code is returned if the software gains can't be locked
+#[get("/software_gain?<c>")]
+fn get_software_gain(c: usize, data_accessor: &State<DataHandler>) -> Result<Json<f64>, BadRequest<String>> {
+    data_accessor.software_gain(c).map(Json)
+}
 
-            match loaded_data {
-                Some(data) => {
-                    match data.get_channel(c) {
-                        Some(channel) => {
-                            let channel_subset = data.get_channel_subset(c).expect("Subset not found!");
-                            let a_scan = channel.slice(s![y, x, ..]).to_vec();
+/// Set the voltage range of a channel
+/// 
+/// The SonoWare header doesn't provide the voltage range, so it has to be set from
+/// the digitizer settings to convert amplitudes into volts.
+/// 
+/// # Arguments
+/// * `c`: Channel index
+/// * `range`: Voltage of full screen height in V, `0` removes the range
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The range is negative or isn't finite
+/// * The voltage ranges can't be locked
+#[post("/voltage_range?<c>&<range>")]
+fn set_voltage_range(c: usize, range: f64, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    if !range.is_finite() || range < 0.0 {
+        return Err(BadRequest(String::from("The voltage range has to be positive!")));
+    }
 
-                            Ok(Json(AScanJson { 
-                                scan: a_scan.clone(),
-                                time_start: channel_subset.min_sample_pos, 
-                                time_step: channel_subset.sample_resolution,
-                                filtered_scan: filter_a_scan(&a_scan).unwrap() //.iter().map(|x| *x).collect()
-                            }))
-                        }
-                        None => {
-                            Err(BadRequest(String::from("Channel not recorded!")))
-                        }
-                    }
-                }
-                None => {
-                    Err(BadRequest(String::from("No data loaded")))
-                }
+    match data_accessor.voltage_ranges.lock() {
+        Ok(mut ranges) => {
+            if range == 0.0 {
+                ranges.remove(&c);
+            }
+            else {
+                ranges.insert(c, range);
             }
+
+            Ok("voltage range updated")
         }
         Err(error) => {
             println!("{}", error);
-            Err(BadRequest(String::from("Dataset already used!")))
+            Err(BadRequest(String::from("Failed to lock voltage ranges")))
         }
     }
 }
 
-/// Returns the header of a loaded dataset
+/// Get the voltage range of a channel
 /// 
 /// # Arguments
-/// * `data_accessor`: Internal handler of the dataset
+/// * `c`: Channel index
+/// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// If no error occurs the `Header` will be returned in JSON representation
+/// The voltage of full screen height in V or `null` if no range has been set
 /// 
 /// # Errors
-/// An error code will be returned if one of the following issues occurs:
-/// * The dataset can't be locked
-/// * No data has been loaded
-#[get("/header")]
-fn get_data_header(data_accessor: &State<DataHandler>) -> Result<Json<data::Header>, BadRequest<String>> {
-    let ds = data_accessor.dataset.lock();
+/// An error code is returned if the voltage ranges can't be locked
+#[get("/voltage_range?<c>")]
+fn get_voltage_range(c: usize, data_accessor: &State<DataHandler>) -> Result<Json<Option<f64>>, BadRequest<String>> {
+    data_accessor.voltage_range(c).map(Json)
+}
 
-    match ds {
-        Ok(dataset) => {
-            let us_data = dataset.as_ref();
+/// Get the material library
+/// 
+/// # Returns
+/// The materials of `materials.json` which can be selected for the loaded dataset
+/// 
+/// # Errors
+/// An error code is returned if the library can't be read or is invalid
+#[get("/materials")]
+fn get_materials() -> Result<Json<MaterialLibrary>, BadRequest<String>> {
+    MaterialLibrary::load().map(Json).map_err(BadRequest)
+}
 
-            match us_data {
-                Some(loaded_data) => {
-                    Ok(Json(loaded_data.header.clone()))
-                }
-                None => {
-                    println!("No data loaded!");
-                    Err(BadRequest(String::from("No data loaded")))
-                }
-            }
+/// Select the material of the loaded dataset
+/// 
+/// The material converts times of flight into depths for gates in mm, depth
+/// D-Scans, B-Scans and exports. The depth is measured from the start of the
+/// A-Scan time axis, so a couplant path can be added as first layer.
+/// The selection is removed when a new dataset is loaded.
+/// 
+/// # Arguments
+/// * `stack`: JSON object with the `layers` (`material` name of the library and
+///   `thickness` in mm, only the last layer may omit it) and the `wave` mode
+///   (`longitudinal` (default) or `transverse`)
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// The resolved depth profile with the velocity of each layer
+/// 
+/// # Errors
+/// An error code is returned if one of the following issues occurs:
+/// * The library can't be read
+/// * A material is unknown or the stack is invalid
+/// * The material can't be locked
+#[post("/material", data = "<stack>")]
+fn set_material(stack: Json<MaterialStack>, data_accessor: &State<DataHandler>) -> Result<Json<DepthProfile>, BadRequest<String>> {
+    let library = MaterialLibrary::load().map_err(BadRequest)?;
+    let profile = stack.resolve(&library).map_err(BadRequest)?;
+
+    match data_accessor.material.lock() {
+        Ok(mut material) => {
+            *material = Some(profile.clone());
+            Ok(Json(profile))
         }
         Err(error) => {
             println!("{}", error);
-            Err(BadRequest(String::from("Failed to lock dataset")))
+            Err(BadRequest(String::from("Failed to lock material")))
         }
     }
 }
 
-/// Get the C-Scan for a specific channel
+/// Get the material of the loaded dataset
 /// 
 /// # Arguments
-/// * `c`: Channel index
-/// * `start`: start index of the aperture
-/// * `end`: end index of the aperture
 /// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// The JSON representation of the C-Scan values as a 2-D-Array
+/// The depth profile or `null` if no material has been selected
 /// 
 /// # Errors
-/// An error code will be returned if one of the following issues occurs:
-/// * The dataset can't be locked
-/// * No data is loaded
-/// * The channel hasn't been recorded
-#[get("/c_scan?<c>&<start>&<end>&<as_decibel>")]
-fn get_c_scan(c: usize, start: usize, end: usize, as_decibel: usize, data_accessor: &State<DataHandler>) -> Result<Json<Vec<Vec<f64>>>, BadRequest<String>> {
-    let ds = data_accessor.dataset.lock();
-
-    match ds {
-        Ok(dataset) => {
-            let us_data = dataset.as_ref();
+/// An error code is returned if the material can't be locked
+#[get("/material")]
+fn get_material(data_accessor: &State<DataHandler>) -> Result<Json<Option<DepthProfile>>, BadRequest<String>> {
+    data_accessor.material().map(Json)
+}
 
-            match us_data {
-                Some(loaded_data) => {
-                    match loaded_data.c_scan(c, start, end, as_decibel == 1) {
-                        Some(c_scan) => { 
-                            Ok(Json(vec_to_2d_list(c_scan.into_raw_vec_and_offset().0.as_mut(), loaded_data.header.samples_x.into()))) 
-                        }
-                        None => {
-                            println!("Failed to create c-scan");
-                            Err(BadRequest(String::from("C-Scan can't be created")))
-                        }
-                    }
-                }
-                None => {
-                    println!("No data loaded");
-                    Err(BadRequest(String::from("No data loaded!")))
-                }
-            }
+/// Remove the material of the loaded dataset
+/// 
+/// # Arguments
+/// * `data_accessor`: Internal handler for the data
+/// 
+/// # Returns
+/// A success message
+/// 
+/// # Errors
+/// An error code is returned if the material can't be locked
+#[delete("/material")]
+fn remove_material(data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<String>> {
+    match data_accessor.material.lock() {
+        Ok(mut material) => {
+            *material = None;
+            Ok("material removed")
         }
         Err(error) => {
             println!("{}", error);
-            Err(BadRequest(String::from("Data already used")))
+            Err(BadRequest(String::from("Failed to lock material")))
         }
     }
 }
 
-/// Get the D-Scan for a specific channel
+/// Estimate the sound velocity from regions of known thickness
 /// 
 /// # Arguments
 /// * `c`: Channel index
-/// * `start`: Start index of the aperture
-/// * `end`: End index of the aperture
+/// * `calibration`: JSON object with the named backwall `gate`, the optional `reference` gate,
+///   the `regions` of known thickness (`roi` as in `/c_scan`, `thickness` in mm and its
+///   optional standard `uncertainty`), the `wave` mode and the optional material name `save`.
+///   One region requires the `reference` gate to measure the time of flight from zero thickness,
+///   several regions (step heights) are fitted by a line and additionally return the time offset.
 /// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
-/// JSON representation of the D-Scan as a 2-D-Array
+/// JSON object containing the velocity in m/s, its standard uncertainty, the time
+/// offset, the measurement of each region and, if saved, the updated library entry
 /// 
 /// # Errors
 /// An error code is returned if one of the following issues occurs:
-/// * The dataset can't be locked
+/// * The dataset or the gates can't be locked
 /// * No data is loaded
+/// * The calibration is invalid or doesn't fit to the named gates
 /// * The channel hasn't been recorded
-#[get("/d_scan?<c>&<start>&<end>")]
-fn get_d_scan(c: usize, start: usize, end: usize, data_accessor: &State<DataHandler>) -> Result<Json<Vec<Vec<u32>>>, BadRequest<String>> {
+/// * A region is invalid or contains no detected echo
+/// * The material library can't be read or written
+#[post("/calibration?<c>", data = "<calibration>")]
+fn calibrate_velocity(c: usize, calibration: Json<VelocityCalibration>, data_accessor: &State<DataHandler>) -> Result<Json<CalibrationResult>, BadRequest<String>> {
+    let gate_config = data_accessor.gate_config(c)?;
+    calibration.validate(&gate_config).map_err(BadRequest)?;
+
     let ds = data_accessor.dataset.lock();
 
     match ds {
         Ok(dataset) => {
             let us_data = dataset.as_ref();
-            
+
             match us_data {
                 Some(loaded_data) => {
-                    match loaded_data.d_scan(c, start, end) {
-                        Some(d_scan) => {
-                            Ok(Json(vec_to_2d_list(d_scan.into_raw_vec_and_offset().0.as_mut(), loaded_data.header.samples_x.into())))
-                        }
-                        None => {
-                            Err(BadRequest(String::from("Failed to generate D-Scan")))
-                        }
+                    let subset = loaded_data.get_channel_subset(c).ok_or(BadRequest(String::from("The channel hasn't been recorded!")))?;
+                    let masks = calibration.regions.iter()
+                        .map(|region| roi_mask(loaded_data, Some(region.roi.clone())).map(|roi| roi.unwrap().mask))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let gate_scans = cached_gate_scans(data_accessor, loaded_data, c, &gate_config, None)
+                        .ok_or(BadRequest(String::from("Failed to generate the gate scans")))?;
+                    let time_of_flight = calibration.time_of_flight(&gate_config, &gate_scans);
+
+                    let mut result = calibration.estimate(&time_of_flight, &masks, subset.sample_resolution as f64 / 1000.0)
+                        .map_err(BadRequest)?;
+
+                    if let Some(name) = &calibration.save {
+                        let mut library = MaterialLibrary::load().map_err(BadRequest)?;
+                        let material = library.set_velocity(name, result.velocity, calibration.wave).map_err(BadRequest)?;
+                        library.save().map_err(BadRequest)?;
+                        result.material = Some(material);
                     }
+
+                    Ok(Json(result))
                 }
                 None => {
                     println!("No data loaded!");
@@ -349,16 +2851,46 @@ fn help() -> Template {
 /// 
 /// # Arguments
 /// * `channel`: Channel index
-/// * `start`: Start index of the aperture
-/// * `end`: End index of the aperture
+/// * `start`: Start of the aperture
+/// * `end`: End of the aperture
 /// * `name`: Export file name
+/// * `mode`: Amplitude measure for the C-Scans (default: `peak`)
+/// * `db_ref`: Reference of the dB scans, see `/c_scan`. `scan_max` refers to each scan.
+/// * `db_value`: Reference value for `db_ref=user`
+/// * `unit`: Unit of additional amplitude scans, `fsh`, `counts` or `volts`, see `/c_scan`
+/// * `method`: Time of flight detection method for the D-Scan (default: `peak`)
+/// * `threshold`: Threshold for the threshold based methods (default: `50`)
+/// * `threshold_unit`: Unit of the threshold (default: `percent`)
+/// * `threshold_ref`: Reference of a dB threshold, see `/d_scan`
+/// * `threshold_ref_value`: Reference value for `threshold_ref=user`
+/// * `iface`: Optional interface gate. If provided, `start` and `end` are relative
+///   to the interface echo.
+/// * `gate_unit`: Unit of `start` and `end`, see `/c_scan`
+/// * `velocity`: Sound velocity in m/s for `gate_unit=mm` and the depth scans, overrides
+///   the selected material
+/// * `thickness`: Optional wall thickness configuration (`thickness.velocity`,
+///   `thickness.gate`, `thickness.reference`) based on the named gates
+/// * `roi`: Optional region of interest as JSON, see `/c_scan`. All exported scans
+///   contain `NaN` outside of the region.
+/// * `defects`: Optional defect detection (`defects.threshold`, `defects.level`,
+///   `defects.reference`, `defects.min_area`), see `/defects`
 /// * `data_accessor`: Internal handler for the data
 /// 
 /// # Returns
 /// Message containing the file name. A ZIP file has been created containing
 /// the following files:
-/// * c_scan.csv
-/// * d_scan.csv
+/// * c_scan_norm.csv
+/// * c_scan_db.csv
+/// * c_scan_<unit>.csv if a unit other than `normalized` is provided
+/// * d_scan.csv with the times of flight in µs after the first sample of the A-Scans
+///   (add `time_offset` of config.json for the absolute time)
+/// * d_scan_depth.csv if a velocity is provided or a material has been selected
+/// * gate_<name>_c_scan_norm.csv, gate_<name>_c_scan_db.csv, gate_<name>_c_scan_<unit>.csv,
+///   gate_<name>_d_scan.csv and gate_<name>_d_scan_depth.csv for each named gate of the channel
+/// * difference_<name>.csv for each configured gate difference
+/// * thickness.csv if a thickness configuration is provided
+/// * indications.csv and indications.json if a defect detection is provided
+/// * statistics.json with the statistics and histograms of the C-, D- and thickness scans
 /// * config.json
 /// 
 /// # Errors
@@ -366,9 +2898,31 @@ fn help() -> Template {
 /// * The dataset can't be locked
 /// * No data is loaded
 /// * The channel hasn't been recorded
+/// * The aperture can't be converted or doesn't fit into the A-Scans
+/// * The region of interest, the dB reference, the unit or the defect detection is invalid
 /// * The output file can't be created
-#[post("/export?<channel>&<start>&<end>&<name>")]
-fn export_data(channel: usize, start: usize, end: usize, name: String, data_accessor: &State<DataHandler>) -> Result<String, BadRequest<String>> {
+#[allow(clippy::too_many_arguments)]
+#[post("/export?<channel>&<start>&<end>&<name>&<mode>&<db_ref>&<db_value>&<unit>&<method>&<threshold>&<threshold_unit>&<threshold_ref>&<threshold_ref_value>&<iface>&<gate_unit>&<velocity>&<thickness>&<roi>&<defects>")]
+fn export_data(channel: usize, start: f64, end: f64, name: String, mode: Option<AmplitudeMode>, db_ref: Option<DecibelReferenceKind>,
+    db_value: Option<f64>, unit: Option<AmplitudeUnit>, method: Option<TofMethod>, threshold: Option<f64>, threshold_unit: Option<ThresholdUnit>,
+    threshold_ref: Option<DecibelReferenceKind>, threshold_ref_value: Option<f64>,
+    iface: Option<InterfaceGate>, gate_unit: Option<GateUnit>, velocity: Option<f64>, thickness: Option<ThicknessConfig>, roi: Option<Roi>,
+    defects: Option<DefectConfig>, data_accessor: &State<DataHandler>) -> Result<String, BadRequest<String>> {
+    let mode = mode.unwrap_or_default();
+    let db_reference = data_accessor.decibel_reference(channel, DecibelReference::from_request(db_ref, db_value).map_err(BadRequest)?)?;
+    let method = method.unwrap_or_default();
+    let threshold = get_threshold(threshold, threshold_unit, threshold_ref, threshold_ref_value)?;
+
+    let gate_config = data_accessor.gate_config(channel)?;
+
+    if let Some(thickness_config) = &thickness {
+        thickness_config.validate(&gate_config).map_err(BadRequest)?;
+    }
+
+    if let Some(defect_config) = &defects {
+        defect_config.validate().map_err(BadRequest)?;
+    }
+
     let ds = data_accessor.dataset.lock();
 
     match ds {
@@ -377,23 +2931,64 @@ fn export_data(channel: usize, start: usize, end: usize, name: String, data_acce
 
             match us_data {
                 Some(loaded_data) => {
+                    let material = data_accessor.depth_profile(velocity)?;
+                    let gate = get_gate(loaded_data, channel, start, end, iface, gate_unit, material.as_ref())?;
                     match loaded_data.get_channel_subset(channel) {
                         Some(header) => {
-                            let c_scan_norm = loaded_data.c_scan(channel, start, end, false).unwrap();
-                            let d_scan_norm = loaded_data.d_scan(channel, start, end).unwrap();
+                            let roi_mask = roi_mask(loaded_data, roi.clone())?;
+                            let unit = unit.unwrap_or_default();
+                            let scale = unit_scale(data_accessor, channel, Some(unit))?;
+                            let c_scan_norm = cached_c_scan(data_accessor, loaded_data, channel, &gate, mode, roi_mask.as_ref()).unwrap();
+                            let d_scan_norm = cached_d_scan(data_accessor, loaded_data, channel, &gate, method, threshold, roi_mask.as_ref()).unwrap();
+
+                            let c_scan_db = db_reference.convert(c_scan_norm.as_ref(), mode, header.gain);
+                            let c_scan_unit = (unit != AmplitudeUnit::Normalized).then(|| scale.convert(c_scan_norm.as_ref(), mode));
+
+                            let gate_scans = cached_gate_scans(data_accessor, loaded_data, channel, &gate_config, roi_mask.as_ref()).unwrap();
+                            let differences = gate_differences(&gate_config, &gate_scans);
+                            let thickness_map = thickness.as_ref().map(|config| config.thickness(&gate_config, &gate_scans));
+                            let indications = defects.as_ref().map(|config| detect_defects(config, &c_scan_db, loaded_data));
+
+                            let mut statistics = BTreeMap::from([
+                                (String::from("c_scan_norm"), ScanStatistics::of(c_scan_norm.as_ref(), &DEFAULT_PERCENTILES, DEFAULT_BINS)),
+                                (String::from("c_scan_db"), ScanStatistics::of(&c_scan_db, &DEFAULT_PERCENTILES, DEFAULT_BINS)),
+                                (String::from("d_scan"), ScanStatistics::of(d_scan_norm.as_ref(), &DEFAULT_PERCENTILES, DEFAULT_BINS))
+                            ]);
+
+                            if let Some(c_scan_unit) = &c_scan_unit {
+                                statistics.insert(format!("c_scan_{}", unit.name()), ScanStatistics::of(c_scan_unit, &DEFAULT_PERCENTILES, DEFAULT_BINS));
+                            }
 
-                            let c_scan_db = loaded_data.c_scan(channel, start, end, true).unwrap();
+                            if let Some(thickness_map) = &thickness_map {
+                                statistics.insert(String::from("thickness"), ScanStatistics::of(thickness_map, &DEFAULT_PERCENTILES, DEFAULT_BINS));
+                            }
 
                             let output_file_path = Path::new("export/").join(format!("{}.zip", name));
 
                             match File::create(output_file_path) {
                                 Ok(file) => {
                                     let output_config = ExportHeader {
-                                        aperture: vec![header.sample_resolution * start as f32 / 1000.0,
-                                            header.sample_resolution * end as f32 / 1000.0],
+                                        aperture: vec![header.sample_resolution * gate.start as f32 / 1000.0,
+                                            header.sample_resolution * gate.end as f32 / 1000.0],
+                                        interface: gate.interface,
+                                        time_offset: header.min_sample_pos,
                                         x_step: loaded_data.header.res_x,
                                         y_step: loaded_data.header.res_y,
-                                        gain: header.gain
+                                        gain: header.gain,
+                                        software_gain: data_accessor.software_gain(channel)?,
+                                        voltage_range: data_accessor.voltage_range(channel)?,
+                                        unit,
+                                        mode,
+                                        db_reference,
+                                        tof_method: method,
+                                        threshold,
+                                        gates: gate_config.clone(),
+                                        thickness: thickness.clone(),
+                                        roi,
+                                        defects: defects.clone(),
+                                        correction: data_accessor.correction(channel)?,
+                                        material: material.clone(),
+                                        thickness_statistics: thickness_map.as_ref().map(Statistics::of)
                                     };
                                     let json_data = serde_json::to_string_pretty(&output_config).unwrap();
 
@@ -403,14 +2998,70 @@ fn export_data(channel: usize, start: usize, end: usize, name: String, data_acce
                                         .unix_permissions(0o755);
 
                                     zip.start_file("c_scan_norm.csv", options).expect("Failed to start c-scan file");
-                                    zip.write_all(array_to_csv::<f64>(c_scan_norm, 0.0, 1.0).as_bytes()).expect("Failed to write c-scan CSV");
+                                    zip.write_all(array_to_csv::<f64>(c_scan_norm.as_ref().clone(), 0.0, 1.0).as_bytes()).expect("Failed to write c-scan CSV");
                                     
                                     zip.start_file("d_scan.csv", options).expect("Failed to start d-scan file");
-                                    zip.write_all(array_to_csv::<u32>(d_scan_norm, 0.0, (header.sample_resolution / 1000.0).into()).as_bytes()).expect("Failed to write d-scan CSV");
+                                    zip.write_all(array_to_csv::<f64>(relative_d_scan(d_scan_norm.as_ref(), header), 0.0, 1.0).as_bytes()).expect("Failed to write d-scan CSV");
+
+                                    if let Some(profile) = &material {
+                                        zip.start_file("d_scan_depth.csv", options).expect("Failed to start d-scan file");
+                                        zip.write_all(array_to_csv::<f64>(depth_scan(d_scan_norm.as_ref(), profile), 0.0, 1.0).as_bytes()).expect("Failed to write d-scan CSV");
+                                    }
 
                                     zip.start_file("c_scan_db.csv", options).expect("Failed to start c-scan file");
                                     zip.write_all(array_to_csv::<f64>(c_scan_db, 0.0, 1.0).as_bytes()).expect("Failed to write c-scan CSV");
 
+                                    if let Some(c_scan_unit) = c_scan_unit {
+                                        zip.start_file(format!("c_scan_{}.csv", unit.name()), options).expect("Failed to start c-scan file");
+                                        zip.write_all(array_to_csv::<f64>(c_scan_unit, 0.0, 1.0).as_bytes()).expect("Failed to write c-scan CSV");
+                                    }
+
+                                    for (named_gate, scan) in gate_config.gates.iter().zip(gate_scans.iter()) {
+                                        let c_scan_db = db_reference.convert(&scan.amplitude, named_gate.mode, header.gain);
+
+                                        zip.start_file(format!("gate_{}_c_scan_norm.csv", named_gate.name), options).expect("Failed to start gate c-scan file");
+                                        zip.write_all(array_to_csv::<f64>(scan.amplitude.clone(), 0.0, 1.0).as_bytes()).expect("Failed to write gate c-scan CSV");
+
+                                        zip.start_file(format!("gate_{}_c_scan_db.csv", named_gate.name), options).expect("Failed to start gate c-scan file");
+                                        zip.write_all(array_to_csv::<f64>(c_scan_db, 0.0, 1.0).as_bytes()).expect("Failed to write gate c-scan CSV");
+
+                                        if unit != AmplitudeUnit::Normalized {
+                                            zip.start_file(format!("gate_{}_c_scan_{}.csv", named_gate.name, unit.name()), options).expect("Failed to start gate c-scan file");
+                                            zip.write_all(array_to_csv::<f64>(scale.convert(&scan.amplitude, named_gate.mode), 0.0, 1.0).as_bytes())
+                                                .expect("Failed to write gate c-scan CSV");
+                                        }
+
+                                        zip.start_file(format!("gate_{}_d_scan.csv", named_gate.name), options).expect("Failed to start gate d-scan file");
+                                        zip.write_all(array_to_csv::<f64>(relative_d_scan(&scan.time, header), 0.0, 1.0).as_bytes()).expect("Failed to write gate d-scan CSV");
+
+                                        if let Some(profile) = &material {
+                                            zip.start_file(format!("gate_{}_d_scan_depth.csv", named_gate.name), options).expect("Failed to start gate d-scan file");
+                                            zip.write_all(array_to_csv::<f64>(depth_scan(&scan.time, profile), 0.0, 1.0).as_bytes())
+                                                .expect("Failed to write gate d-scan CSV");
+                                        }
+                                    }
+
+                                    for (difference_name, scan) in differences {
+                                        zip.start_file(format!("difference_{}.csv", difference_name), options).expect("Failed to start difference file");
+                                        zip.write_all(array_to_csv::<f64>(scan, 0.0, 1.0).as_bytes()).expect("Failed to write difference CSV");
+                                    }
+
+                                    if let Some(thickness_map) = thickness_map {
+                                        zip.start_file("thickness.csv", options).expect("Failed to start thickness file");
+                                        zip.write_all(array_to_csv::<f64>(thickness_map, 0.0, 1.0).as_bytes()).expect("Failed to write thickness CSV");
+                                    }
+
+                                    if let Some(indications) = &indications {
+                                        zip.start_file("indications.csv", options).expect("Failed to start indications file");
+                                        zip.write_all(defect::indications_to_csv(&indications.indications).as_bytes()).expect("Failed to write indications CSV");
+
+                                        zip.start_file("indications.json", options).expect("Failed to start indications file");
+                                        zip.write_all(serde_json::to_string_pretty(indications).unwrap().as_bytes()).expect("Failed to write indications JSON");
+                                    }
+
+                                    zip.start_file("statistics.json", options).expect("Failed to create statistics file");
+                                    zip.write_all(serde_json::to_string_pretty(&statistics).unwrap().as_bytes()).expect("Failed to write statistics file");
+
                                     zip.start_file("config.json", options).expect("Failed to create config file");
                                     zip.write_all(json_data.as_bytes()).expect("Failed to write JSON config file.");
 
@@ -516,18 +3167,25 @@ async fn import_data(data: Data<'_>) -> Result<Json<Vec<Vec<f32>>>, BadRequest<S
 /// * The provided data is invalid
 #[post("/data/sonoware", data = "<data_request>")]
 async fn load_data(data_request: Data<'_>, data_accessor: &State<DataHandler>) -> Result<&'static str, BadRequest<&'static str>> {
-    let data = data::UsData::load_sonoware(data_request.open(1024.gibibytes())
-        .into_bytes().await.unwrap().value);
+    let bytes = data_request.open(1024.gibibytes()).into_bytes().await.unwrap().value;
+    let identity = cache::dataset_identity(&bytes);
+    let data = data::UsData::load_sonoware(bytes);
 
     match data_accessor.dataset.lock() {
         Ok(mut data_handler) => {
             match data {
                 Some(us_data) => {
                     *data_handler = Some(us_data);
+                    data_accessor.set_cache_dataset(identity);
+                    data_accessor.clear_material();
+                    data_accessor.reset_volumes(data_handler.as_ref());
                     Ok("loading successful")
                 }
                 None => {
                     *data_handler = None;
+                    data_accessor.clear_cache();
+                    data_accessor.clear_material();
+                    data_accessor.reset_volumes(None);
         
                     println!("Failed to load data");
                     Err(BadRequest("Loading provided data failed"))
@@ -571,19 +3229,20 @@ fn get_state(data_accessor: &State<DataHandler>) -> &'static str {
 
 #[launch]
 fn rocket() -> _ {
-    match fs::create_dir("export") {
-        Ok(_) => {}
-        Err(_) => { }
-    }
+    let _ = fs::create_dir("export");
     
     let _ = open::that("http://localhost:8000");
 
     rocket::build().mount("/", routes![index, load_data, get_state, get_a_scan, get_data_header, get_c_scan,
-        get_d_scan, export_data, help, exit_program, import_data, reference])
+        get_d_scan, export_data, help, exit_program, import_data, reference, set_gates, get_gates, get_gate_scans,
+        get_thickness, get_statistics, get_defects, evaluate_acceptance, get_b_scan, get_line_b_scan, get_projection,
+        get_c_scan_stack, start_volume, get_volume_status, remove_volume, set_correction, get_correction, remove_correction,
+        set_software_gain, get_software_gain, set_voltage_range, get_voltage_range, get_materials, set_material, get_material, remove_material, calibrate_velocity, get_c_scan_tile, get_channel_a_scans, get_a_scan_batch])
         .mount("/js", FileServer::from("./static_files/js/"))
         .mount("/css", FileServer::from("./static_files/css/"))
         .mount("/img", FileServer::from("./static_files/img"))
         .attach(Template::fairing())
         .configure(Config::figment())
-        .manage(DataHandler { dataset: Mutex::new(None) })
+        .manage(DataHandler { dataset: Mutex::new(None), gates: Mutex::new(HashMap::new()), corrections: Mutex::new(HashMap::new()),
+            software_gains: Mutex::new(HashMap::new()), voltage_ranges: Mutex::new(HashMap::new()), material: Mutex::new(None), cache: Mutex::new(ScanCache::new(MAX_CACHE_BYTES)), volumes: Arc::new(Mutex::new(VolumeStore::default())) })
 }
@@ -0,0 +1,273 @@
+use std::fs::File;
+
+use serde::{Serialize, Deserialize};
+
+use crate::thickness::time_to_thickness;
+
+/// Path of the material library
+const LIBRARY_PATH: &str = "materials.json";
+
+/// Acoustic properties of a material
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    /// Name of the material, e.g. `steel`
+    pub name: String,
+    /// Longitudinal sound velocity in m/s
+    pub longitudinal_velocity: f64,
+    /// Transverse sound velocity in m/s, not defined for liquids
+    pub transverse_velocity: Option<f64>,
+    /// Density in kg/m³
+    pub density: Option<f64>
+}
+
+/// Materials which can be selected for a dataset
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MaterialLibrary {
+    /// List of materials with unique names
+    pub materials: Vec<Material>
+}
+
+impl MaterialLibrary {
+    /// Loads the library
+    ///
+    /// # Returns
+    /// The materials described by `materials.json`
+    ///
+    /// # Errors
+    /// A message is returned if the file can't be read or is invalid
+    pub fn load() -> Result<MaterialLibrary, String> {
+        let file = File::open(LIBRARY_PATH).map_err(|error| format!("Failed to open the material library: {}", error))?;
+        let library: MaterialLibrary = serde_json::from_reader(file).map_err(|error| format!("Invalid material library: {}", error))?;
+
+        library.validate()?;
+        Ok(library)
+    }
+
+    /// Saves the library
+    ///
+    /// # Errors
+    /// A message is returned if the library is invalid or the file can't be written
+    pub fn save(&self) -> Result<(), String> {
+        self.validate()?;
+
+        let file = File::create(LIBRARY_PATH).map_err(|error| format!("Failed to create the material library: {}", error))?;
+        serde_json::to_writer_pretty(file, self).map_err(|error| format!("Failed to write the material library: {}", error))
+    }
+
+    /// Sets the sound velocity of a material
+    ///
+    /// # Arguments
+    /// * `name`: Name of the material, a new material is added if it doesn't exist yet
+    /// * `velocity`: Sound velocity in m/s
+    /// * `wave`: Wave mode of the velocity
+    ///
+    /// # Returns
+    /// A copy of the updated material
+    ///
+    /// # Errors
+    /// A message is returned if a transverse velocity is set for a new material,
+    /// which requires a longitudinal velocity
+    pub fn set_velocity(&mut self, name: &str, velocity: f64, wave: WaveMode) -> Result<Material, String> {
+        let index = match self.materials.iter().position(|material| material.name == name) {
+            Some(index) => index,
+            None if wave == WaveMode::Longitudinal => {
+                self.materials.push(Material { name: String::from(name), longitudinal_velocity: velocity, transverse_velocity: None, density: None });
+                self.materials.len() - 1
+            }
+            None => return Err(format!("Material {} is unknown, a longitudinal velocity is required first!", name))
+        };
+
+        let material = &mut self.materials[index];
+
+        match wave {
+            WaveMode::Longitudinal => material.longitudinal_velocity = velocity,
+            WaveMode::Transverse => material.transverse_velocity = Some(velocity)
+        }
+
+        Ok(material.clone())
+    }
+
+    /// Checks if the library is valid
+    ///
+    /// # Errors
+    /// A message is returned if a name is used twice or a property isn't positive
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, material) in self.materials.iter().enumerate() {
+            if self.materials.iter().position(|other| other.name == material.name) != Some(index) {
+                return Err(format!("Material {} is defined twice!", material.name));
+            }
+
+            let properties = std::iter::once(material.longitudinal_velocity).chain(material.transverse_velocity).chain(material.density);
+
+            if properties.into_iter().any(|value| !value.is_finite() || value <= 0.0) {
+                return Err(format!("The properties of material {} have to be positive!", material.name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a material
+    ///
+    /// # Arguments
+    /// * `name`: Name of the material
+    pub fn get(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+}
+
+/// Wave mode the depth is calculated with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaveMode {
+    /// Longitudinal (compression) waves
+    #[default]
+    Longitudinal,
+    /// Transverse (shear) waves
+    Transverse
+}
+
+/// Layer of a material stack
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    /// Name of the material inside the library
+    pub material: String,
+    /// Thickness in mm, only the last layer may be unbounded
+    pub thickness: Option<f64>
+}
+
+/// Layers of materials the sound passes from the start of the A-Scan time axis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MaterialStack {
+    /// Layers in the order they are passed
+    pub layers: Vec<Layer>,
+    /// Wave mode of the sound (default: `longitudinal`)
+    #[serde(default)]
+    pub wave: WaveMode
+}
+
+/// Layer of a resolved material stack
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DepthLayer {
+    /// Material of the layer
+    pub material: Material,
+    /// Thickness in mm, **None** for an unbounded layer
+    pub thickness: Option<f64>,
+    /// Sound velocity of the wave mode in m/s
+    pub velocity: f64
+}
+
+/// Conversion between pulse-echo times of flight and depths
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DepthProfile {
+    /// Wave mode of the sound
+    pub wave: WaveMode,
+    /// Layers in the order they are passed, the last one is unbounded
+    pub layers: Vec<DepthLayer>
+}
+
+impl MaterialStack {
+    /// Resolves the materials of the stack
+    ///
+    /// # Arguments
+    /// * `library`: Material library
+    ///
+    /// # Errors
+    /// A message is returned if the stack is empty, a material is unknown or has
+    /// no velocity for the wave mode, or a thickness is invalid
+    pub fn resolve(&self, library: &MaterialLibrary) -> Result<DepthProfile, String> {
+        if self.layers.is_empty() {
+            return Err(String::from("The material stack needs at least one layer!"));
+        }
+
+        let last = self.layers.len() - 1;
+
+        let layers = self.layers.iter().enumerate().map(|(index, layer)| {
+            let material = library.get(&layer.material).ok_or(format!("Unknown material {}!", layer.material))?;
+
+            let velocity = match self.wave {
+                WaveMode::Longitudinal => material.longitudinal_velocity,
+                WaveMode::Transverse => material.transverse_velocity
+                    .ok_or(format!("Material {} has no transverse velocity!", material.name))?
+            };
+
+            let thickness = match layer.thickness {
+                Some(thickness) if !thickness.is_finite() || thickness <= 0.0 => {
+                    return Err(format!("The thickness of layer {} has to be positive!", index + 1));
+                }
+                None if index != last => return Err(String::from("Only the last layer may be unbounded!")),
+                _ if index == last => None,
+                thickness => thickness
+            };
+
+            Ok(DepthLayer { material: material.clone(), thickness, velocity })
+        }).collect::<Result<Vec<DepthLayer>, String>>()?;
+
+        Ok(DepthProfile { wave: self.wave, layers })
+    }
+}
+
+impl DepthProfile {
+    /// Creates a profile of a single unbounded material
+    ///
+    /// # Arguments
+    /// * `velocity`: Sound velocity in m/s
+    pub fn homogeneous(velocity: f64) -> DepthProfile {
+        DepthProfile {
+            wave: WaveMode::default(),
+            layers: vec![DepthLayer {
+                material: Material { name: String::from("custom"), longitudinal_velocity: velocity, transverse_velocity: None, density: None },
+                thickness: None,
+                velocity
+            }]
+        }
+    }
+
+    /// Converts a pulse-echo time of flight into a depth
+    ///
+    /// # Arguments
+    /// * `time`: Time of flight in µs
+    ///
+    /// # Returns
+    /// The depth in mm, negative times are converted with the first layer
+    pub fn depth(&self, time: f64) -> f64 {
+        let mut remaining = time;
+        let mut depth = 0.0;
+
+        for layer in &self.layers {
+            match layer.thickness {
+                Some(thickness) if remaining > 2.0 * thickness / layer.velocity * 1000.0 => {
+                    remaining -= 2.0 * thickness / layer.velocity * 1000.0;
+                    depth += thickness;
+                }
+                _ => return depth + time_to_thickness(remaining, layer.velocity)
+            }
+        }
+
+        depth
+    }
+
+    /// Converts a depth into a pulse-echo time of flight
+    ///
+    /// # Arguments
+    /// * `depth`: Depth in mm
+    ///
+    /// # Returns
+    /// The time of flight in µs, the inverse of `depth`
+    pub fn time(&self, depth: f64) -> f64 {
+        let mut remaining = depth;
+        let mut time = 0.0;
+
+        for layer in &self.layers {
+            match layer.thickness {
+                Some(thickness) if remaining > thickness => {
+                    remaining -= thickness;
+                    time += 2.0 * thickness / layer.velocity * 1000.0;
+                }
+                _ => return time + 2.0 * remaining / layer.velocity * 1000.0
+            }
+        }
+
+        time
+    }
+}
@@ -0,0 +1,135 @@
+use ndarray::Array2;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Serialize, Deserialize};
+
+/// Coordinate system of a region of interest
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoiUnit {
+    /// (column, row) indices of the datapoints
+    #[default]
+    Index,
+    /// Position in mm relative to the first datapoint
+    Mm
+}
+
+/// Region of interest of arbitrary shape
+///
+/// Passed as JSON, e.g. `{"shape": "polygon", "points": [[0, 0], [20, 0], [10, 15]], "unit": "mm"}`.
+/// All bounds are inclusive.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Roi {
+    /// Axis aligned rectangle
+    Rect {
+        /// Left border
+        x_start: f64,
+        /// Right border
+        x_end: f64,
+        /// Upper border
+        y_start: f64,
+        /// Lower border
+        y_end: f64,
+        /// Coordinate system of the borders
+        #[serde(default)]
+        unit: RoiUnit
+    },
+    /// Polygon which is closed automatically
+    Polygon {
+        /// Corners as (x, y) coordinates
+        points: Vec<(f64, f64)>,
+        /// Coordinate system of the corners
+        #[serde(default)]
+        unit: RoiUnit
+    }
+}
+
+/// Validated region of interest with the datapoints inside of it
+pub struct RoiMask {
+    /// Requested region
+    pub roi: Roi,
+    /// `true` for each datapoint inside the region, shape `[rows, columns]`
+    pub mask: Array2<bool>
+}
+
+impl Roi {
+    /// Determines the datapoints inside the region
+    ///
+    /// # Arguments
+    /// * `rows`: Number of rows
+    /// * `cols`: Number of columns
+    /// * `res_x`: Distance between two columns in mm
+    /// * `res_y`: Distance between two rows in mm
+    ///
+    /// # Errors
+    /// A message is returned if a coordinate isn't finite, the rectangle borders are
+    /// swapped, the polygon has less than three corners or no datapoint is inside the region
+    pub fn resolve(self, rows: usize, cols: usize, res_x: f64, res_y: f64) -> Result<RoiMask, String> {
+        let (unit, coordinates): (RoiUnit, Vec<(f64, f64)>) = match &self {
+            Roi::Rect { x_start, x_end, y_start, y_end, unit } => (*unit, vec![(*x_start, *y_start), (*x_end, *y_end)]),
+            Roi::Polygon { points, unit } => (*unit, points.clone())
+        };
+
+        if coordinates.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+            return Err(String::from("The region of interest contains invalid coordinates!"));
+        }
+
+        // scaling of the datapoint indices into the coordinate system of the region
+        let (scale_x, scale_y) = match unit {
+            RoiUnit::Index => (1.0, 1.0),
+            RoiUnit::Mm => (res_x, res_y)
+        };
+
+        let mask = match &self {
+            Roi::Rect { x_start, x_end, y_start, y_end, .. } => {
+                if x_start > x_end || y_start > y_end {
+                    return Err(String::from("The borders of the region of interest are swapped!"));
+                }
+
+                Array2::from_shape_fn((rows, cols), |(row, col)| {
+                    (*x_start..=*x_end).contains(&(col as f64 * scale_x)) && (*y_start..=*y_end).contains(&(row as f64 * scale_y))
+                })
+            }
+            Roi::Polygon { points, .. } => {
+                if points.len() < 3 {
+                    return Err(String::from("The polygon needs at least three points!"));
+                }
+
+                Array2::from_shape_fn((rows, cols), |(row, col)| contains(points, col as f64 * scale_x, row as f64 * scale_y))
+            }
+        };
+
+        if !mask.iter().any(|inside| *inside) {
+            return Err(String::from("No datapoint is inside the region of interest!"));
+        }
+
+        Ok(RoiMask { roi: self, mask })
+    }
+}
+
+impl<'v> FromFormField<'v> for Roi {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        serde_json::from_str(field.value).map_err(|error| form::Error::validation(error.to_string()).into())
+    }
+}
+
+/// Checks if a point is inside a polygon or on its border
+fn contains(points: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+
+    for (index, &(x1, y1)) in points.iter().enumerate() {
+        let (x0, y0) = points[(index + points.len() - 1) % points.len()];
+
+        // point on the edge
+        let cross = (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0);
+        if cross.abs() <= 1e-9 * (1.0 + (x1 - x0).hypot(y1 - y0)) && x >= x0.min(x1) && x <= x0.max(x1) && y >= y0.min(y1) && y <= y0.max(y1) {
+            return true;
+        }
+
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
@@ -0,0 +1,307 @@
+use ndarray::{Array, Array1, Array2, ArrayView1, ArrayView3, Axis, s};
+use rocket::FromFormField;
+use serde::{Serialize, Deserialize};
+
+use crate::data::{AScanFilter, Samples, UsData};
+use crate::signal::envelope;
+
+/// Smallest step along a polyline as fraction of the smallest scan resolution
+pub const MIN_STEP_FRACTION: f64 = 0.1;
+
+/// Maximum number of A-Scans along a polyline
+pub const MAX_LINE_SAMPLES: usize = 8192;
+
+/// Scan axis along which a B-Scan is taken
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanAxis {
+    /// Along a row (horizontal direction)
+    #[field(value = "x")]
+    X,
+    /// Along a column (vertical direction)
+    #[field(value = "y")]
+    Y
+}
+
+/// Filters an A-Scan and calculates its envelope if requested
+///
+/// # Arguments
+/// * `a_scan`: Complete A-Scan
+/// * `position`: (row, column) index of the A-Scan
+/// * `samples`: Source of the filtered samples
+/// * `as_envelope`: The envelope should be returned instead of the filtered A-Scan
+///
+/// # Returns
+/// The processed A-Scan
+pub fn process_a_scan(a_scan: ArrayView1<f64>, position: (usize, usize), samples: Samples, as_envelope: bool) -> Vec<f64> {
+    match samples {
+        Samples::Raw(filter) => {
+            let filtered = filter.apply(a_scan);
+
+            if as_envelope {
+                envelope(&filtered)
+            }
+            else {
+                filtered
+            }
+        }
+        Samples::Filtered(volume) => volume.a_scan(position.0, position.1, as_envelope)
+    }
+}
+
+/// Generates the B-Scan along a row or column of a channel
+///
+/// # Arguments
+/// * `data`: Loaded dataset
+/// * `channel`: Channel number
+/// * `axis`: Axis along which the B-Scan is taken
+/// * `index`: Row index for `ScanAxis::X` or column index for `ScanAxis::Y`
+/// * `samples`: Source of the filtered samples
+/// * `as_envelope`: Envelope should be used instead of the filtered A-Scans
+///
+/// # Returns
+/// If the channel has been recorded and the index is valid a 2-D array of shape
+/// `[positions, samples]` will be returned, else **None**
+pub fn b_scan(data: &UsData, channel: usize, axis: ScanAxis, index: usize, samples: Samples, as_envelope: bool) -> Option<Array2<f64>> {
+    let array = data.get_channel(channel)?;
+
+    let line_axis = match axis {
+        ScanAxis::X => Axis(0),
+        ScanAxis::Y => Axis(1)
+    };
+
+    if index >= array.len_of(line_axis) {
+        return None;
+    }
+
+    let line = array.index_axis(line_axis, index);
+
+    let mut scan = Array::zeros((line.shape()[0], line.shape()[1]));
+
+    for (position, a_scan) in line.outer_iter().enumerate() {
+        let grid_position = match axis {
+            ScanAxis::X => (index, position),
+            ScanAxis::Y => (position, index)
+        };
+
+        let processed = process_a_scan(a_scan, grid_position, samples, as_envelope);
+        scan.slice_mut(s![position, ..]).assign(&ArrayView1::from(&processed));
+    }
+
+    Some(scan)
+}
+
+/// Interpolation between neighboring A-Scans
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// A-Scan of the nearest datapoint
+    #[default]
+    Nearest,
+    /// Bilinear interpolation between the four surrounding A-Scans
+    Bilinear
+}
+
+impl Interpolation {
+    /// Interpolates the A-Scan at a position between the datapoints
+    ///
+    /// # Arguments
+    /// * `array`: Samples of the channel with shape `[rows, columns, samples]`
+    /// * `x`: Fractional column index inside the scan
+    /// * `y`: Fractional row index inside the scan
+    pub fn a_scan(self, array: ArrayView3<f64>, x: f64, y: f64) -> Array1<f64> {
+        let (rows, cols) = (array.shape()[0], array.shape()[1]);
+
+        match self {
+            Interpolation::Nearest => array.slice(s![y.round() as usize, x.round() as usize, ..]).to_owned(),
+            Interpolation::Bilinear => {
+                let (col, row) = ((x.floor() as usize).min(cols - 1), (y.floor() as usize).min(rows - 1));
+                let (next_col, next_row) = ((col + 1).min(cols - 1), (row + 1).min(rows - 1));
+                let (dx, dy) = (x - col as f64, y - row as f64);
+
+                &array.slice(s![row, col, ..]) * ((1.0 - dx) * (1.0 - dy))
+                    + &array.slice(s![row, next_col, ..]) * (dx * (1.0 - dy))
+                    + &array.slice(s![next_row, col, ..]) * ((1.0 - dx) * dy)
+                    + &array.slice(s![next_row, next_col, ..]) * (dx * dy)
+            }
+        }
+    }
+}
+
+/// Polyline over the C-Scan along which a B-Scan is taken
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Polyline {
+    /// Points of the polyline as (column, row) indices, fractional values are allowed
+    pub points: Vec<(f64, f64)>,
+    /// Interpolation between neighboring A-Scans
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Distance between two A-Scans along the path in mm (default: smallest scan resolution)
+    pub step: Option<f64>,
+    /// Envelope should be used instead of the filtered A-Scans
+    #[serde(default)]
+    pub envelope: bool
+}
+
+impl Polyline {
+    /// Checks if the polyline can be sampled on the scan grid
+    ///
+    /// # Arguments
+    /// * `samples_x`: Number of columns
+    /// * `samples_y`: Number of rows
+    /// * `res_x`: Distance between two columns in mm
+    /// * `res_y`: Distance between two rows in mm
+    ///
+    /// # Errors
+    /// A message is returned if the polyline has less than two points, a point is
+    /// outside of the scan grid, the step is smaller than `MIN_STEP_FRACTION` of the
+    /// smallest resolution or the path has more than `MAX_LINE_SAMPLES` A-Scans
+    pub fn validate(&self, samples_x: usize, samples_y: usize, res_x: f64, res_y: f64) -> Result<(), String> {
+        if self.points.len() < 2 {
+            return Err(String::from("The polyline needs at least two points!"));
+        }
+
+        for &(x, y) in &self.points {
+            if !(0.0..=(samples_x as f64 - 1.0)).contains(&x) || !(0.0..=(samples_y as f64 - 1.0)).contains(&y) {
+                return Err(format!("Point ({}, {}) is outside of the scan!", x, y));
+            }
+        }
+
+        let min_step = res_x.min(res_y) * MIN_STEP_FRACTION;
+        let step = self.step.unwrap_or(res_x.min(res_y));
+
+        if step.is_nan() || step < min_step {
+            return Err(format!("The step has to be at least {} mm!", min_step));
+        }
+
+        if self.length(res_x, res_y) / step + 1.0 > MAX_LINE_SAMPLES as f64 {
+            return Err(format!("The polyline exceeds {} A-Scans, increase the step!", MAX_LINE_SAMPLES));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path length in mm
+    ///
+    /// # Arguments
+    /// * `res_x`: Distance between two columns in mm
+    /// * `res_y`: Distance between two rows in mm
+    pub fn length(&self, res_x: f64, res_y: f64) -> f64 {
+        self.points.windows(2).map(|segment| ((segment[1].0 - segment[0].0) * res_x).hypot((segment[1].1 - segment[0].1) * res_y)).sum()
+    }
+
+    /// Samples the polyline with a constant distance
+    ///
+    /// # Arguments
+    /// * `res_x`: Distance between two columns in mm
+    /// * `res_y`: Distance between two rows in mm
+    ///
+    /// # Returns
+    /// A list containing the path length in mm and the (column, row) position of each sample
+    pub fn sample(&self, res_x: f64, res_y: f64) -> Vec<(f64, (f64, f64))> {
+        let step = self.step.unwrap_or(res_x.min(res_y));
+        let mut samples = vec![];
+        let mut segment_start = 0.0;
+        let mut distance = 0.0;
+
+        for segment in self.points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+            let length = ((x1 - x0) * res_x).hypot((y1 - y0) * res_y);
+
+            // tolerance avoids a duplicate of the end point due to rounding errors
+            while distance < segment_start + length - step * 1e-9 {
+                let ratio = (distance - segment_start) / length;
+                samples.push((distance, (x0 + ratio * (x1 - x0), y0 + ratio * (y1 - y0))));
+                distance += step;
+            }
+
+            segment_start += length;
+        }
+
+        samples.push((segment_start, *self.points.last().unwrap()));
+
+        samples
+    }
+}
+
+/// Generates the B-Scan along a polyline
+///
+/// # Arguments
+/// * `data`: Loaded dataset
+/// * `channel`: Channel number
+/// * `polyline`: Validated polyline over the C-Scan
+/// * `filter`: Filter of the channel
+///
+/// # Returns
+/// If the channel has been recorded a 2-D array of shape `[positions, samples]`
+/// and the path length of each position in mm will be returned, else **None**
+pub fn line_b_scan(data: &UsData, channel: usize, polyline: &Polyline, filter: &AScanFilter) -> Option<(Array2<f64>, Vec<f64>)> {
+    let array = data.get_channel(channel)?;
+    let samples = polyline.sample(data.header.res_x as f64, data.header.res_y as f64);
+    let mut scan = Array::zeros((samples.len(), array.shape()[2]));
+
+    for (position, (_, (x, y))) in samples.iter().enumerate() {
+        let a_scan = polyline.interpolation.a_scan(array.view(), *x, *y);
+
+        // interpolated A-Scans have no precomputed counterpart
+        let processed = process_a_scan(a_scan.view(), (0, 0), Samples::Raw(filter), polyline.envelope);
+        scan.slice_mut(s![position, ..]).assign(&ArrayView1::from(&processed));
+    }
+
+    Some((scan, samples.into_iter().map(|(distance, _)| distance).collect()))
+}
+
+/// Generates the maximum amplitude projection of a channel
+///
+/// # Arguments
+/// * `data`: Loaded dataset
+/// * `channel`: Channel number
+/// * `axis`: Axis along which the maximum is taken; `ScanAxis::X` results in a
+///   row-time image (side view), `ScanAxis::Y` in a column-time image (end view)
+/// * `start`: First sample of the time gate
+/// * `end`: First sample after the time gate
+/// * `mask`: Optional mask of the datapoints inside the region of interest
+/// * `samples`: Source of the filtered samples
+/// * `as_envelope`: Envelope should be used instead of the rectified A-Scans
+///
+/// # Returns
+/// If the channel has been recorded the index of the first position and a 2-D array
+/// of shape `[positions, end - start]` will be returned, else **None**. The positions
+/// span the datapoints inside the mask, positions without such datapoints are `NaN`.
+#[allow(clippy::too_many_arguments)]
+pub fn projection(data: &UsData, channel: usize, axis: ScanAxis, start: usize, end: usize, mask: Option<&Array2<bool>>, samples: Samples,
+    as_envelope: bool) -> Option<(usize, Array2<f64>)> {
+    let array = data.get_channel(channel)?;
+    let (rows, cols, _) = array.dim();
+    let inside = |row: usize, col: usize| mask.is_none_or(|mask| mask[[row, col]]);
+
+    let position_of = |row: usize, col: usize| match axis {
+        ScanAxis::X => row,
+        ScanAxis::Y => col
+    };
+
+    let positions: Vec<usize> = (0..rows).flat_map(|row| (0..cols).map(move |col| (row, col)))
+        .filter(|(row, col)| inside(*row, *col))
+        .map(|(row, col)| position_of(row, col))
+        .collect();
+    let first = *positions.iter().min()?;
+    let last = *positions.iter().max()?;
+
+    let mut scan: Array2<f64> = Array::from_elem((last - first + 1, end - start), f64::NAN);
+
+    for (row_index, row) in array.outer_iter().enumerate() {
+        for (col_index, col) in row.outer_iter().enumerate() {
+            if !inside(row_index, col_index) {
+                continue;
+            }
+
+            let processed = process_a_scan(col, (row_index, col_index), samples, as_envelope);
+
+            for (maximum, value) in scan.row_mut(position_of(row_index, col_index) - first).iter_mut().zip(&processed[start..end]) {
+                *maximum = maximum.max(value.abs());
+            }
+        }
+    }
+
+    Some((first, scan))
+}
@@ -0,0 +1,496 @@
+use ndarray::{Array, ArrayBase, Data, Dimension};
+use rocket::{FromForm, FromFormField};
+use rustfft::{FftPlanner, num_complex::Complex};
+use serde::{Serialize, Deserialize};
+
+/// Amplitude measure which is evaluated inside the aperture of an A-Scan
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum AmplitudeMode {
+    /// Maximum value (positive peak)
+    #[default]
+    #[field(value = "peak")]
+    Peak,
+    /// Maximum of the absolute values
+    #[field(value = "abs_peak")]
+    AbsPeak,
+    /// Minimum value (negative peak)
+    #[field(value = "neg_peak")]
+    NegPeak,
+    /// Difference between maximum and minimum
+    #[field(value = "peak_to_peak")]
+    PeakToPeak,
+    /// Root mean square
+    #[field(value = "rms")]
+    Rms,
+    /// Sum of squares
+    #[field(value = "energy")]
+    Energy,
+    /// Mean of the absolute values
+    #[field(value = "mean_rectified")]
+    MeanRectified,
+    /// Sum of the envelope values
+    #[field(value = "integrated_envelope")]
+    IntegratedEnvelope
+}
+
+impl AmplitudeMode {
+    /// Evaluates the amplitude measure of a window
+    ///
+    /// # Arguments
+    /// * `window`: Samples inside the aperture
+    ///
+    /// # Returns
+    /// The value of the measure. An empty window results in `0.0`.
+    pub fn evaluate(self, window: &[f64]) -> f64 {
+        if window.is_empty() {
+            return 0.0;
+        }
+
+        let count = window.len() as f64;
+
+        match self {
+            AmplitudeMode::Peak => window.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b)),
+            AmplitudeMode::AbsPeak => window.iter().fold(0.0, |a: f64, &b| a.max(b.abs())),
+            AmplitudeMode::NegPeak => window.iter().fold(f64::INFINITY, |a, &b| a.min(b)),
+            AmplitudeMode::PeakToPeak => {
+                let (min, max) = window.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &b| (min.min(b), max.max(b)));
+                max - min
+            }
+            AmplitudeMode::Rms => (window.iter().map(|x| x * x).sum::<f64>() / count).sqrt(),
+            AmplitudeMode::Energy => window.iter().map(|x| x * x).sum(),
+            AmplitudeMode::MeanRectified => window.iter().map(|x| x.abs()).sum::<f64>() / count,
+            AmplitudeMode::IntegratedEnvelope => envelope(window).iter().sum()
+        }
+    }
+
+    /// Check if the measure is a power quantity
+    ///
+    /// # Returns
+    /// `true` if the measure has to be converted into dB with `10 * log10`
+    /// instead of `20 * log10`
+    pub fn is_power(self) -> bool {
+        matches!(self, AmplitudeMode::Energy)
+    }
+
+    /// Converts a value of this measure into dB relative to full screen height
+    ///
+    /// Values below a single 16 bit step are limited to it, which avoids `-inf`.
+    ///
+    /// # Arguments
+    /// * `value`: Value of the measure based on normalized samples
+    fn level(self, value: f64) -> f64 {
+        if self.is_power() {
+            10.0 * value.abs().max(self.step()).log10()
+        }
+        else {
+            20.0 * value.abs().max(self.step()).log10()
+        }
+    }
+
+    /// Returns the value of this measure for a single 16 bit step
+    fn step(self) -> f64 {
+        let step = 1.0 / (f64::powi(2.0, 15) - 1.0);
+
+        if self.is_power() {
+            step * step
+        }
+        else {
+            step
+        }
+    }
+}
+
+/// Reference level (0 dB) of amplitudes in dB
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecibelReference {
+    /// A single 16 bit step reduced by the gain of the channel
+    #[default]
+    Raw,
+    /// Full screen height, i.e. the maximum of the normalized samples
+    Fsh,
+    /// Maximum absolute value of the converted scan
+    ScanMax,
+    /// User selected value of the amplitude measure, e.g. of a reference echo
+    User(f64)
+}
+
+/// Kind of the dB reference selected by a request
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum DecibelReferenceKind {
+    /// See `DecibelReference::Raw`
+    #[default]
+    #[field(value = "raw")]
+    Raw,
+    /// See `DecibelReference::Fsh`
+    #[field(value = "fsh")]
+    Fsh,
+    /// See `DecibelReference::ScanMax`
+    #[field(value = "scan_max")]
+    ScanMax,
+    /// See `DecibelReference::User`
+    #[field(value = "user")]
+    User
+}
+
+/// Conversion of values of an amplitude measure into dB
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecibelScale {
+    /// Converted amplitude measure
+    mode: AmplitudeMode,
+    /// Level of the reference relative to full screen height in dB
+    offset: f64
+}
+
+impl DecibelScale {
+    /// Converts a value into dB
+    ///
+    /// # Arguments
+    /// * `value`: Value of the measure based on normalized samples
+    ///
+    /// # Returns
+    /// The value in dB, `NaN` stays `NaN`
+    pub fn apply(&self, value: f64) -> f64 {
+        if value.is_nan() {
+            return f64::NAN;
+        }
+
+        self.mode.level(value) - self.offset
+    }
+
+    /// Converts a dB value into a value of the measure, the inverse of `apply`
+    ///
+    /// # Arguments
+    /// * `level`: Value in dB
+    ///
+    /// # Returns
+    /// The value of the measure based on normalized samples
+    pub fn linear(&self, level: f64) -> f64 {
+        if self.mode.is_power() {
+            f64::powf(10.0, (level + self.offset) / 10.0)
+        }
+        else {
+            f64::powf(10.0, (level + self.offset) / 20.0)
+        }
+    }
+}
+
+impl DecibelReference {
+    /// Creates the reference from request parameters
+    ///
+    /// # Arguments
+    /// * `kind`: Kind of the reference (default: `raw`)
+    /// * `value`: Reference value of the `user` reference
+    ///
+    /// # Errors
+    /// A message is returned if the `user` reference has no positive finite value
+    pub fn from_request(kind: Option<DecibelReferenceKind>, value: Option<f64>) -> Result<DecibelReference, String> {
+        let reference = match kind {
+            None | Some(DecibelReferenceKind::Raw) => DecibelReference::Raw,
+            Some(DecibelReferenceKind::Fsh) => DecibelReference::Fsh,
+            Some(DecibelReferenceKind::ScanMax) => DecibelReference::ScanMax,
+            Some(DecibelReferenceKind::User) => DecibelReference::User(value.ok_or("The user reference requires a value!")?)
+        };
+
+        reference.validate()?;
+        Ok(reference)
+    }
+
+    /// Checks if the reference is valid
+    ///
+    /// # Errors
+    /// A message is returned if a user reference isn't positive and finite
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            DecibelReference::User(value) if !value.is_finite() || *value <= 0.0 => Err(String::from("The dB reference value has to be positive!")),
+            _ => Ok(())
+        }
+    }
+
+    /// Creates the conversion into dB
+    ///
+    /// # Arguments
+    /// * `mode`: Amplitude measure of the converted values
+    /// * `gain`: Gain of the channel
+    /// * `scan_max`: Returns the maximum absolute value of the scan, only called for `scan_max`
+    pub fn scale<F: FnOnce() -> f64>(self, mode: AmplitudeMode, gain: f64, scan_max: F) -> DecibelScale {
+        let offset = match self {
+            DecibelReference::Raw => mode.level(mode.step()) + gain,
+            DecibelReference::Fsh => 0.0,
+            DecibelReference::ScanMax => mode.level(scan_max()),
+            DecibelReference::User(value) => mode.level(value)
+        };
+
+        DecibelScale { mode, offset }
+    }
+
+    /// Converts a scan of linear values into dB
+    ///
+    /// # Arguments
+    /// * `scan`: Linear values of the amplitude measure
+    /// * `mode`: Amplitude measure of the scan
+    /// * `gain`: Gain of the channel
+    ///
+    /// # Returns
+    /// The scan in dB. `NaN` datapoints stay `NaN`, the maximum of the `scan_max`
+    /// reference ignores them.
+    pub fn convert<S, D>(self, scan: &ArrayBase<S, D>, mode: AmplitudeMode, gain: f64) -> Array<f64, D>
+        where S: Data<Elem = f64>, D: Dimension {
+        let scale = self.scale(mode, gain, || {
+            scan.iter().filter(|value| !value.is_nan()).fold(f64::NAN, |max, value| max.max(value.abs()))
+        });
+
+        scan.mapv(|value| scale.apply(value))
+    }
+}
+
+/// Calculates the envelope of a signal using the Hilbert transform
+///
+/// # Arguments
+/// * `signal`: Samples of the signal
+///
+/// # Returns
+/// The magnitude of the analytic signal with the same length as `signal`
+pub fn envelope(signal: &[f64]) -> Vec<f64> {
+    let length = signal.len();
+
+    if length == 0 {
+        return vec![];
+    }
+
+    let mut planner = FftPlanner::<f64>::new();
+    let fft = planner.plan_fft_forward(length);
+    let ifft = planner.plan_fft_inverse(length);
+
+    let mut spectrum: Vec<Complex<f64>> = signal.iter().map(|&x| Complex::new(x, 0.0)).collect();
+    fft.process(&mut spectrum);
+
+    // keep DC (and Nyquist), double positive and remove negative frequencies
+    let half = length / 2;
+    for (index, value) in spectrum.iter_mut().enumerate().skip(1) {
+        if index < half || (index == half && length % 2 == 1) {
+            *value *= 2.0;
+        }
+        else if index > half {
+            *value = Complex::new(0.0, 0.0);
+        }
+    }
+
+    ifft.process(&mut spectrum);
+
+    spectrum.iter().map(|value| value.norm() / length as f64).collect()
+}
+
+/// Method for detecting the time of flight inside the aperture of an A-Scan
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum TofMethod {
+    /// Position of the maximum value
+    #[default]
+    #[field(value = "peak")]
+    Peak,
+    /// First crossing of the threshold by the rectified signal
+    #[field(value = "threshold")]
+    Threshold,
+    /// First zero crossing after the threshold has been exceeded
+    #[field(value = "zero_crossing")]
+    ZeroCrossing,
+    /// Position of the maximum of the envelope
+    #[field(value = "envelope_peak")]
+    EnvelopePeak,
+    /// Position of the maximum refined by a parabolic interpolation
+    #[field(value = "interpolated_peak")]
+    InterpolatedPeak
+}
+
+impl TofMethod {
+    /// Detects the time of flight inside a window
+    ///
+    /// # Arguments
+    /// * `window`: Samples inside the aperture
+    /// * `threshold`: Linear threshold used by the threshold based methods
+    ///
+    /// # Returns
+    /// The position inside the window in (fractional) samples or **None**
+    /// if no echo has been detected
+    pub fn detect(self, window: &[f64], threshold: f64) -> Option<f64> {
+        if window.is_empty() {
+            return None;
+        }
+
+        match self {
+            TofMethod::Peak => Some(argmax(window) as f64),
+            TofMethod::Threshold => threshold_crossing(window, threshold),
+            TofMethod::ZeroCrossing => {
+                let crossing = threshold_crossing(window, threshold)?.ceil() as usize;
+                let sign = window[crossing].signum();
+
+                (crossing + 1..window.len()).find(|&i| window[i].signum() != sign)
+                    .map(|i| interpolate_crossing(window[i - 1], window[i], 0.0) + (i - 1) as f64)
+            }
+            TofMethod::EnvelopePeak => Some(argmax(&envelope(window)) as f64),
+            TofMethod::InterpolatedPeak => {
+                let index = argmax(window);
+
+                if index == 0 || index == window.len() - 1 {
+                    return Some(index as f64);
+                }
+
+                let (left, center, right) = (window[index - 1], window[index], window[index + 1]);
+                let denominator = left - 2.0 * center + right;
+
+                if denominator == 0.0 {
+                    Some(index as f64)
+                }
+                else {
+                    Some(index as f64 + 0.5 * (left - right) / denominator)
+                }
+            }
+        }
+    }
+}
+
+/// Unit of a detection threshold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdUnit {
+    /// Percent of the full scale
+    #[default]
+    #[field(value = "percent")]
+    Percent,
+    /// Decibel in the scaling of the C-Scan
+    #[field(value = "db")]
+    Decibel
+}
+
+/// Detection threshold for the time of flight
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, FromForm)]
+pub struct Threshold {
+    /// Threshold value
+    #[field(default = 50.0)]
+    pub value: f64,
+    /// Unit of `value`
+    #[field(default = ThresholdUnit::Percent)]
+    pub unit: ThresholdUnit,
+    /// Reference of a dB threshold (default: `raw`), `scan_max` isn't supported
+    #[serde(default)]
+    #[field(default = DecibelReferenceKind::Raw)]
+    pub db_ref: DecibelReferenceKind,
+    /// Reference value for `db_ref=user`
+    #[serde(default)]
+    pub db_value: Option<f64>
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Threshold { value: 50.0, unit: ThresholdUnit::Percent, db_ref: DecibelReferenceKind::Raw, db_value: None }
+    }
+}
+
+impl Threshold {
+    /// Checks if the threshold is valid
+    ///
+    /// # Errors
+    /// A message is returned if the value isn't finite or the dB reference is invalid
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.value.is_finite() {
+            return Err(String::from("The threshold has to be finite!"));
+        }
+
+        if self.unit == ThresholdUnit::Decibel {
+            if self.db_ref == DecibelReferenceKind::ScanMax {
+                return Err(String::from("A threshold can't refer to the scan maximum!"));
+            }
+
+            DecibelReference::from_request(Some(self.db_ref), self.db_value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts the threshold into a value of the normalized samples
+    ///
+    /// A dB threshold is converted with the scale of its reference, requires a valid threshold.
+    ///
+    /// # Arguments
+    /// * `gain`: Gain of the channel
+    /// * `dac`: `true` if the samples are corrected by a DAC curve, a dB threshold
+    ///   then refers to the curve instead of its reference
+    ///
+    /// # Returns
+    /// The threshold as linear amplitude
+    pub fn linear(self, gain: f64, dac: bool) -> f64 {
+        match self.unit {
+            ThresholdUnit::Percent => self.value / 100.0,
+            ThresholdUnit::Decibel if dac => DecibelReference::Fsh.scale(AmplitudeMode::AbsPeak, gain, || f64::NAN).linear(self.value),
+            ThresholdUnit::Decibel => {
+                let reference = DecibelReference::from_request(Some(self.db_ref), self.db_value).unwrap_or(DecibelReference::User(f64::NAN));
+                reference.scale(AmplitudeMode::AbsPeak, gain, || f64::NAN).linear(self.value)
+            }
+        }
+    }
+}
+
+/// Selects samples of a trace which preserve its shape in a plot
+///
+/// The trace is split into buckets of equal length and the minimum and
+/// maximum of each bucket are kept in their original order.
+///
+/// # Arguments
+/// * `values`: Samples of the trace
+/// * `max_points`: Maximum number of selected samples, at least `2`
+///
+/// # Returns
+/// The sorted indices of the selected samples
+pub fn min_max_indices(values: &[f64], max_points: usize) -> Vec<usize> {
+    if values.len() <= max_points {
+        return (0..values.len()).collect();
+    }
+
+    let buckets = max_points / 2;
+    let mut indices = Vec::with_capacity(2 * buckets);
+
+    for bucket in 0..buckets {
+        let (start, end) = (bucket * values.len() / buckets, (bucket + 1) * values.len() / buckets);
+        let (min, max) = (start..end).fold((start, start), |(min, max), i| {
+            (if values[i] < values[min] { i } else { min }, if values[i] > values[max] { i } else { max })
+        });
+
+        indices.push(min.min(max));
+
+        if min != max {
+            indices.push(min.max(max));
+        }
+    }
+
+    indices
+}
+
+/// Returns the index of the first maximum
+fn argmax(values: &[f64]) -> usize {
+    values.iter().enumerate()
+        .fold((0, f64::NEG_INFINITY), |(index, max), (i, &value)| if value > max { (i, value) } else { (index, max) }).0
+}
+
+/// Returns the position where the rectified signal crosses the threshold
+/// with linear interpolation between the neighboring samples
+fn threshold_crossing(window: &[f64], threshold: f64) -> Option<f64> {
+    let index = window.iter().position(|value| value.abs() >= threshold)?;
+
+    if index == 0 {
+        return Some(0.0);
+    }
+
+    Some(interpolate_crossing(window[index - 1].abs(), window[index].abs(), threshold) + (index - 1) as f64)
+}
+
+/// Returns the fractional position between two samples where `level` is crossed
+fn interpolate_crossing(previous: f64, next: f64, level: f64) -> f64 {
+    if next == previous {
+        return 0.0;
+    }
+
+    ((level - previous) / (next - previous)).clamp(0.0, 1.0)
+}
@@ -0,0 +1,177 @@
+use ndarray::{ArrayBase, Data, Dim};
+use rocket::FromFormField;
+use serde::Serialize;
+
+/// Percentiles which are calculated if none have been requested
+pub const DEFAULT_PERCENTILES: [f64; 4] = [5.0, 25.0, 75.0, 95.0];
+
+/// Default number of histogram bins
+pub const DEFAULT_BINS: usize = 64;
+
+/// Scan the statistics are calculated of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum StatisticsSource {
+    /// C-Scan amplitudes
+    #[field(value = "c_scan")]
+    CScan,
+    /// D-Scan times of flight in µs
+    #[field(value = "d_scan")]
+    DScan,
+    /// Wall thickness map in mm
+    #[field(value = "thickness")]
+    Thickness
+}
+
+/// Value of a percentile
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Percentile {
+    /// Percentile between `0` and `100`
+    pub percentile: f64,
+    /// Value below which `percentile` percent of the values are
+    pub value: f64
+}
+
+/// Statistics of a scan
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Statistics {
+    /// Number of valid values
+    pub count: usize,
+    /// Minimum value
+    pub min: f64,
+    /// Maximum value
+    pub max: f64,
+    /// Mean value
+    pub mean: f64,
+    /// Median value
+    pub median: f64,
+    /// Population standard deviation
+    pub std: f64,
+    /// Requested percentiles
+    pub percentiles: Vec<Percentile>
+}
+
+impl Statistics {
+    /// Calculates the statistics of a scan with the default percentiles
+    ///
+    /// # Arguments
+    /// * `scan`: 2-D-Array with the values of the scan
+    ///
+    /// # Returns
+    /// The statistics of all finite values. If the scan contains no valid
+    /// values, `count` is zero and all other values are `NaN`.
+    pub fn of<S>(scan: &ArrayBase<S, Dim<[usize; 2]>>) -> Statistics where S: Data<Elem = f64> {
+        Statistics::with_percentiles(scan, &DEFAULT_PERCENTILES)
+    }
+
+    /// Calculates the statistics of a scan
+    ///
+    /// # Arguments
+    /// * `scan`: 2-D-Array with the values of the scan
+    /// * `percentiles`: Percentiles between `0` and `100` which should be calculated
+    ///
+    /// # Returns
+    /// The statistics of all finite values. Percentiles are linearly interpolated
+    /// between the sorted values. If the scan contains no valid values, `count`
+    /// is zero and all other values are `NaN`.
+    pub fn with_percentiles<S>(scan: &ArrayBase<S, Dim<[usize; 2]>>, percentiles: &[f64]) -> Statistics where S: Data<Elem = f64> {
+        let mut values: Vec<f64> = scan.iter().copied().filter(|value| value.is_finite()).collect();
+
+        if values.is_empty() {
+            return Statistics {
+                count: 0,
+                min: f64::NAN,
+                max: f64::NAN,
+                mean: f64::NAN,
+                median: f64::NAN,
+                std: f64::NAN,
+                percentiles: percentiles.iter().map(|&percentile| Percentile { percentile, value: f64::NAN }).collect()
+            };
+        }
+
+        values.sort_by(f64::total_cmp);
+
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / count as f64;
+
+        Statistics {
+            count,
+            min: values[0],
+            max: values[count - 1],
+            mean,
+            median: percentile_of(&values, 50.0),
+            std: variance.sqrt(),
+            percentiles: percentiles.iter().map(|&percentile| Percentile { percentile, value: percentile_of(&values, percentile) }).collect()
+        }
+    }
+}
+
+/// Histogram of a scan
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Histogram {
+    /// Borders of the bins, one more than the number of bins
+    pub edges: Vec<f64>,
+    /// Number of values inside each bin
+    pub counts: Vec<usize>
+}
+
+impl Histogram {
+    /// Calculates the histogram of a scan
+    ///
+    /// # Arguments
+    /// * `scan`: 2-D-Array with the values of the scan
+    /// * `bins`: Number of equally wide bins between the minimum and maximum value
+    ///
+    /// # Returns
+    /// The histogram of all finite values. The last bin includes the maximum.
+    /// If the scan contains no valid values the histogram is empty.
+    pub fn of<S>(scan: &ArrayBase<S, Dim<[usize; 2]>>, bins: usize) -> Histogram where S: Data<Elem = f64> {
+        let (min, max) = scan.iter().filter(|value| value.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| (min.min(value), max.max(value)));
+
+        if bins == 0 || min > max {
+            return Histogram { edges: vec![], counts: vec![] };
+        }
+
+        let width = (max - min) / bins as f64;
+        let mut counts = vec![0; bins];
+
+        for &value in scan.iter().filter(|value| value.is_finite()) {
+            let bin = if width > 0.0 { ((value - min) / width) as usize } else { 0 };
+            counts[bin.min(bins - 1)] += 1;
+        }
+
+        Histogram { edges: (0..=bins).map(|index| min + width * index as f64).collect(), counts }
+    }
+}
+
+/// Statistics and histogram of a scan
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScanStatistics {
+    /// Statistics of the valid values
+    pub statistics: Statistics,
+    /// Histogram of the valid values
+    pub histogram: Histogram
+}
+
+impl ScanStatistics {
+    /// Calculates the statistics and the histogram of a scan
+    ///
+    /// # Arguments
+    /// * `scan`: 2-D-Array with the values of the scan
+    /// * `percentiles`: Percentiles between `0` and `100` which should be calculated
+    /// * `bins`: Number of histogram bins
+    pub fn of<S>(scan: &ArrayBase<S, Dim<[usize; 2]>>, percentiles: &[f64], bins: usize) -> ScanStatistics where S: Data<Elem = f64> {
+        ScanStatistics { statistics: Statistics::with_percentiles(scan, percentiles), histogram: Histogram::of(scan, bins) }
+    }
+}
+
+/// Returns a linearly interpolated percentile of sorted values
+fn percentile_of(sorted: &[f64], percentile: f64) -> f64 {
+    let position = percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
@@ -2,9 +2,31 @@
 mod tests {
     use std::fs::{self, File};
     use std::io::Read;
-    use ndarray::s;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use ndarray::{array, s, Array2, Array3, Axis};
+    use rocket::http::{Accept, ContentType, Status};
+    use rocket::Route;
+    use rocket::local::blocking::Client;
 
-    use crate::data::UsData;
+    use crate::acceptance::{AcceptanceProfile, AcceptanceRule, AcceptanceScans};
+    use crate::amplitude::{self, AmplitudeUnit};
+    use crate::binary::{BinaryArray, ResponseFormat};
+    use crate::cache::{self, ScanCache};
+    use crate::calibration::{CalibrationRegion, VelocityCalibration};
+    use crate::correction::{CorrectionCurve, CurveKind, CurvePoint};
+    use crate::data::{AScanFilter, Samples, UsData};
+    use crate::defect::{self, DefectConfig, DefectLevel};
+    use crate::gate::{Gate, GateConfig, InterfaceGate, TimeSlices};
+    use crate::material::{DepthProfile, Layer, MaterialLibrary, MaterialStack, WaveMode};
+    use crate::roi::{Roi, RoiUnit};
+    use crate::section::{self, Interpolation, Polyline, ScanAxis};
+    use crate::signal::{self, AmplitudeMode, DecibelReference, DecibelReferenceKind, Threshold, ThresholdUnit, TofMethod};
+    use crate::statistics::{Histogram, Statistics};
+    use crate::thickness::time_to_thickness;
+    use crate::tile::{self, TileAggregation};
+    use crate::volume::{FilteredVolume, VolumeStore};
 
     const DATA_DIR: &str = "test_scans";
 
@@ -23,6 +45,794 @@ mod tests {
         run_test_on("test_scans/AScanDummy_91_56.itx", 91, 56);
     }
 
+    #[test]
+    fn amplitude_modes() {
+        let window = [0.0, 0.5, -0.75, 0.25];
+
+        assert_eq!(AmplitudeMode::Peak.evaluate(&window), 0.5);
+        assert_eq!(AmplitudeMode::AbsPeak.evaluate(&window), 0.75);
+        assert_eq!(AmplitudeMode::NegPeak.evaluate(&window), -0.75);
+        assert_eq!(AmplitudeMode::PeakToPeak.evaluate(&window), 1.25);
+        assert_eq!(AmplitudeMode::Energy.evaluate(&window), 0.875);
+        assert_eq!(AmplitudeMode::MeanRectified.evaluate(&window), 0.375);
+        assert!((AmplitudeMode::Rms.evaluate(&window) - (0.875f64 / 4.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tof_methods() {
+        let window = [0.0, 0.1, 0.4, -0.2, 0.8, 0.6, -0.5, 0.0];
+
+        assert_eq!(TofMethod::Peak.detect(&window, 0.5), Some(4.0));
+        assert!((TofMethod::Threshold.detect(&window, 0.3).unwrap() - (1.0 + 0.2 / 0.3)).abs() < 1e-12);
+        assert!((TofMethod::ZeroCrossing.detect(&window, 0.3).unwrap() - (2.0 + 0.4 / 0.6)).abs() < 1e-12);
+        assert_eq!(TofMethod::Threshold.detect(&window, 0.9), None);
+
+        let interpolated = TofMethod::InterpolatedPeak.detect(&window, 0.5).unwrap();
+        assert!(interpolated > 3.5 && interpolated < 4.5);
+    }
+
+    #[test]
+    fn thickness_statistics() {
+        assert!((time_to_thickness(2.0, 5920.0) - 5.92).abs() < 1e-12);
+
+        let statistics = Statistics::of(&array![[1.0, f64::NAN], [3.0, 2.0]]);
+        assert_eq!(statistics.count, 3);
+        assert_eq!(statistics.min, 1.0);
+        assert_eq!(statistics.max, 3.0);
+        assert_eq!(statistics.mean, 2.0);
+        assert_eq!(statistics.median, 2.0);
+        assert!((statistics.std - (2.0f64 / 3.0).sqrt()).abs() < 1e-12);
+
+        let scan = array![[0.0, 1.0, 2.0, 3.0], [4.0, f64::NEG_INFINITY, f64::NAN, 10.0]];
+        let statistics = Statistics::with_percentiles(&scan, &[0.0, 25.0, 90.0]);
+        assert_eq!(statistics.count, 6);
+        assert_eq!(statistics.median, 2.5);
+        assert_eq!(statistics.percentiles.iter().map(|percentile| percentile.value).collect::<Vec<f64>>(), vec![0.0, 1.25, 7.0]);
+
+        let histogram = Histogram::of(&scan, 4);
+        assert_eq!(histogram.edges, vec![0.0, 2.5, 5.0, 7.5, 10.0]);
+        assert_eq!(histogram.counts, vec![3, 2, 0, 1]);
+        assert!(Histogram::of(&array![[f64::NAN]], 4).counts.is_empty());
+    }
+
+    #[test]
+    fn c_scan_stack_layout() {
+        let slices = TimeSlices { start: 2, end: 12, width: 4, step: 3 };
+        assert_eq!(slices.windows(), vec![(2, 6), (5, 9), (8, 12)]);
+        assert_eq!(slices.count(), 3);
+        assert!(slices.validate(11).is_err());
+        assert!(TimeSlices { start: usize::MAX, end: 12, width: 4, step: 3 }.validate(12).is_err());
+        assert!(TimeSlices { start: 0, end: 1000, width: 1, step: 1 }.validate(1000).is_err());
+        assert!(TimeSlices { start: 0, end: 1000, width: 1, step: 2 }.validate(1000).is_ok());
+    }
+
+    #[test]
+    fn binary_array() {
+        let data = vec![1.5, -2.0, 0.0, f32::NAN, 1e-3, f32::MAX];
+        let bytes = BinaryArray { shape: vec![2, 3], data: data.clone(), info: serde_json::json!({"x_step": 0.5}) }.to_bytes().unwrap();
+
+        let header_length = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let header: serde_json::Value = serde_json::from_slice(&bytes[4..4 + header_length]).unwrap();
+        assert_eq!(header, serde_json::json!({"shape": [2, 3], "dtype": "f32", "info": {"x_step": 0.5}}));
+
+        let values: Vec<f32> = bytes[4 + header_length..].chunks_exact(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap())).collect();
+        assert_eq!(bytes.len(), 4 + header_length + 4 * 6);
+        assert_eq!(values.iter().map(|value| value.to_bits()).collect::<Vec<_>>(), data.iter().map(|value| value.to_bits()).collect::<Vec<_>>());
+        assert_eq!(&bytes[4 + header_length..4 + header_length + 4], &[0x00, 0x00, 0xc0, 0x3f]);
+
+        let format = |accept: &str| ResponseFormat::from_accept(Some(&accept.parse::<Accept>().unwrap()));
+        assert_eq!(ResponseFormat::from_accept(None), ResponseFormat::Json);
+        assert_eq!(format("*/*"), ResponseFormat::Json);
+        assert_eq!(format("application/octet-stream"), ResponseFormat::Binary);
+        assert_eq!(format("application/json, application/octet-stream"), ResponseFormat::Json);
+        assert_eq!(format("application/octet-stream, application/json"), ResponseFormat::Binary);
+        assert_eq!(format("application/json;q=0.5, application/octet-stream"), ResponseFormat::Binary);
+        assert_eq!(format("*/*;q=0.8, application/octet-stream;q=0.9"), ResponseFormat::Binary);
+        assert_eq!(format("application/json, application/octet-stream;q=0.9"), ResponseFormat::Json);
+        assert_eq!(format("application/octet-stream;q=0, */*"), ResponseFormat::Json);
+    }
+
+    #[test]
+    fn scan_cache_eviction() {
+        let scan_bytes = 4 * std::mem::size_of::<f64>();
+        let mut cache = ScanCache::new(2 * scan_bytes);
+        cache.set_dataset(cache::dataset_identity(b"first"));
+        let keys: Vec<String> = (0..3).map(|channel| cache.key("c_scan", &(channel, 10, 60))).collect();
+
+        for (value, key) in keys.iter().enumerate() {
+            cache.insert(key.clone(), Array2::from_elem((2, 2), value as f64));
+        }
+
+        assert!(cache.get::<Array2<f64>>(&keys[0]).is_none());
+        assert_eq!(cache.get::<Array2<f64>>(&keys[2]).unwrap()[[0, 0]], 2.0);
+        assert!(cache.get::<Array2<f32>>(&keys[1]).is_none());
+
+        let large = cache.insert(cache.key("c_scan", &(3, 10, 60)), Array2::<f64>::zeros((3, 3)));
+        assert_eq!(large.len(), 9);
+        assert!(cache.get::<Array2<f64>>(&keys[1]).is_some());
+
+        cache.set_dataset(cache::dataset_identity(b"first"));
+        assert!(cache.get::<Array2<f64>>(&keys[1]).is_some());
+
+        cache.set_dataset(cache::dataset_identity(b"second"));
+        assert!(cache.get::<Array2<f64>>(&keys[1]).is_none());
+        assert_ne!(cache.key("c_scan", &(1, 10, 60)), keys[1]);
+    }
+
+    #[test]
+    fn tile_pyramid() {
+        assert_eq!(tile::levels(256, 100), 1);
+        assert_eq!(tile::levels(300, 1000), 3);
+
+        let scan = array![[1.0, 3.0, 5.0], [f64::NAN, 2.0, 7.0], [4.0, 0.0, f64::NAN]];
+
+        let max = tile::downsample(&scan, 2, TileAggregation::Max);
+        assert_eq!(max.row(0).to_vec(), vec![3.0, 7.0]);
+        assert_eq!(max[[1, 0]], 4.0);
+        assert!(max[[1, 1]].is_nan());
+
+        let mean = tile::downsample(&scan, 2, TileAggregation::Mean);
+        assert_eq!(mean.row(0).to_vec(), vec![2.0, 6.0]);
+        assert_eq!(mean[[1, 0]], 2.0);
+        assert!(mean[[1, 1]].is_nan());
+
+        assert_eq!(tile::tile(&scan, 0, 0).unwrap().dim(), (3, 3));
+        assert!(tile::tile(&scan, 1, 0).is_none());
+
+        // indices whose position overflows don't wrap around to the first tile
+        assert!(tile::tile(&scan, 1 << (usize::BITS - 8), 0).is_none());
+        assert!(tile::tile(&scan, 0, usize::MAX).is_none());
+        assert!(crate::tile_start(usize::MAX / tile::TILE_SIZE, 2).is_err());
+        assert_eq!(crate::tile_start(3, 2).unwrap(), 6 * tile::TILE_SIZE);
+    }
+
+    #[test]
+    fn min_max_downsampling() {
+        let trace = [0.0, 5.0, -1.0, 2.0, 2.0, 2.0, 9.0, -3.0];
+
+        assert_eq!(signal::min_max_indices(&trace, 8), (0..8).collect::<Vec<usize>>());
+        assert_eq!(signal::min_max_indices(&trace, 4), vec![1, 2, 6, 7]);
+        assert_eq!(signal::min_max_indices(&trace[3..6], 2), vec![0]);
+    }
+
+    #[test]
+    fn roi_masks() {
+        let rect = Roi::Rect { x_start: 1.0, x_end: 2.0, y_start: 0.0, y_end: 0.5, unit: RoiUnit::Mm };
+        let mask = rect.resolve(3, 6, 0.5, 0.5).unwrap().mask;
+        assert_eq!(mask.iter().filter(|inside| **inside).count(), 6);
+        assert!(mask[[0, 2]] && mask[[1, 4]] && !mask[[2, 2]] && !mask[[0, 5]]);
+
+        let polygon: Roi = serde_json::from_str(r#"{"shape": "polygon", "points": [[0, 0], [4, 0], [0, 4]]}"#).unwrap();
+        let mask = polygon.resolve(5, 5, 1.0, 1.0).unwrap().mask;
+        assert!(mask[[0, 4]] && mask[[2, 2]] && !mask[[3, 2]]);
+        assert_eq!(mask.iter().filter(|inside| **inside).count(), 15);
+
+        let outside = Roi::Rect { x_start: 10.0, x_end: 12.0, y_start: 0.0, y_end: 1.0, unit: RoiUnit::Index };
+        assert!(outside.resolve(3, 6, 1.0, 1.0).is_err());
+        assert!(Roi::Polygon { points: vec![(0.0, 0.0), (1.0, 1.0)], unit: RoiUnit::Index }.resolve(3, 6, 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn defect_segmentation() {
+        let c_scan_db = array![
+            [0.0, 12.0, 0.0, 0.0, f64::NAN],
+            [0.0, 0.0, 8.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0, 10.0]
+        ];
+
+        let indications = defect::detect(&c_scan_db, 6.0, 0.5, 1.0, 0.0);
+        assert_eq!(indications.len(), 2);
+        assert_eq!(indications[0].datapoints, 2);
+        assert_eq!(indications[0].area, 1.0);
+        assert_eq!((indications[0].x_min, indications[0].x_max, indications[0].y_max), (0.5, 1.0, 1.0));
+        assert_eq!((indications[0].centroid_x, indications[0].centroid_y), (0.75, 0.5));
+        assert_eq!((indications[0].peak, indications[0].peak_x), (12.0, 0.5));
+        assert_eq!((indications[0].length_6db, indications[0].width_6db, indications[0].area_6db), (1.0, 2.0, 1.0));
+        assert_eq!(indications[1].peak_y, 2.0);
+
+        assert_eq!(defect::detect(&c_scan_db, 6.0, 0.5, 1.0, 0.75).len(), 1);
+
+        let config = DefectConfig { threshold: 3.0, level: Some(DefectLevel::Drop), reference: None, min_area: None };
+        assert_eq!(config.threshold_db(&c_scan_db), 9.0);
+        assert!(DefectConfig { threshold: -3.0, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn acceptance_evaluation() {
+        let profile: AcceptanceProfile = serde_json::from_str(r#"{
+            "name": "part",
+            "indications": {
+                "gate": {"start": 0, "end": 10}, "threshold": 6.0, "max_size": 1.5,
+                "zones": [{"name": "left", "roi": {"shape": "rect", "x_start": 0, "x_end": 1, "y_start": 0, "y_end": 2}, "max_area": 1.5}]
+            },
+            "backwall": {"gate": "B", "reference": 20.0, "max_loss": 6.0}
+        }"#).unwrap();
+
+        let c_scan_db = array![[0.0, 12.0, 0.0, 0.0], [0.0, 9.0, 0.0, 0.0], [0.0, 0.0, 0.0, 10.0]];
+        let backwall_db = array![[20.0, 20.0, 20.0, 13.0], [20.0, 20.0, 20.0, 20.0], [20.0, f64::NAN, 18.0, 20.0]];
+        let scans = AcceptanceScans { c_scan_db: Some(&c_scan_db), thickness: None, backwall_db: Some(&backwall_db), res_x: 1.0, res_y: 1.0 };
+
+        let result = profile.evaluate(&scans).unwrap();
+        assert!(!result.passed);
+        assert_eq!(result.indication_count, 2);
+
+        let rules: Vec<AcceptanceRule> = result.violations.iter().map(|violation| violation.rule).collect();
+        assert_eq!(rules, vec![AcceptanceRule::IndicationSize, AcceptanceRule::ZoneArea, AcceptanceRule::BackwallLoss]);
+        assert_eq!(result.violations[0].indications.len(), 1);
+        assert_eq!(result.violations[0].value, 2.0);
+        assert_eq!(result.violations[1].value, 2.0);
+        assert_eq!((result.violations[2].value, result.violations[2].area, result.violations[2].missing_area), (7.0, Some(2.0), Some(1.0)));
+
+        // the aperture has to fit into the A-Scans
+        let gates: GateConfig = serde_json::from_str(r#"{"gates": [{"name": "B", "start": 40, "end": 80}]}"#).unwrap();
+        assert!(profile.validate(&gates, 10).is_ok());
+        assert!(profile.validate(&gates, 9).is_err());
+
+        // a missing back-wall echo alone fails the profile
+        let backwall_db = array![[20.0, f64::NAN], [20.0, 20.0]];
+        let profile = AcceptanceProfile { indications: None, ..profile };
+        let result = profile.evaluate(&AcceptanceScans { c_scan_db: None, thickness: None, backwall_db: Some(&backwall_db), res_x: 0.5, res_y: 1.0 }).unwrap();
+        assert!(!result.passed);
+        assert!(result.violations[0].value.is_nan());
+        assert_eq!((result.violations[0].area, result.violations[0].missing_area), (Some(0.5), Some(0.5)));
+    }
+
+    #[test]
+    fn correction_curve() {
+        let points = vec![CurvePoint { time: 1.0, level: 0.0 }, CurvePoint { time: 3.0, level: 12.0 }];
+        let tcg = CorrectionCurve { kind: CurveKind::Tcg, points: points.clone() };
+        assert!(tcg.validate().is_ok());
+        assert_eq!(tcg.gain(0.0), 0.0);
+        assert_eq!(tcg.gain(2.5), 9.0);
+        assert_eq!(tcg.gain(5.0), 12.0);
+
+        let dac = CorrectionCurve { kind: CurveKind::Dac, points };
+        assert_eq!(dac.gain(2.0), -6.0);
+
+        let unordered = CorrectionCurve { kind: CurveKind::Tcg, points: vec![CurvePoint { time: 2.0, level: 0.0 }, CurvePoint { time: 2.0, level: 1.0 }] };
+        assert!(unordered.validate().is_err());
+        assert!(CorrectionCurve { kind: CurveKind::Dac, points: vec![] }.validate().is_err());
+    }
+
+    #[test]
+    fn polyline_sampling() {
+        let polyline = Polyline { points: vec![(0.0, 0.0), (2.0, 0.0), (2.0, 1.0)], interpolation: Interpolation::Bilinear, step: Some(0.5), envelope: false };
+        assert!(polyline.validate(3, 2, 0.5, 1.0).is_ok());
+        assert_eq!(polyline.length(0.5, 1.0), 2.0);
+
+        let positions = polyline.sample(0.5, 1.0);
+        assert_eq!(positions.len(), 5);
+        assert_eq!(positions[1], (0.5, (1.0, 0.0)));
+        assert_eq!(positions[3], (1.5, (2.0, 0.5)));
+        assert_eq!(positions[4], (2.0, (2.0, 1.0)));
+
+        assert!(Polyline { step: Some(0.04), ..polyline.clone() }.validate(3, 2, 0.5, 1.0).is_err());
+        assert!(Polyline { step: Some(f64::NAN), ..polyline.clone() }.validate(3, 2, 0.5, 1.0).is_err());
+        assert!(Polyline { points: vec![(0.0, 0.0), (2.0, 2.0)], ..polyline.clone() }.validate(3, 2, 0.5, 1.0).is_err());
+
+        let long = Polyline { points: vec![(0.0, 0.0), (9999.0, 0.0)], step: None, ..polyline };
+        assert!(long.validate(10000, 1, 1.0, 1.0).is_err());
+        assert!(Polyline { step: Some(2.0), ..long }.validate(10000, 1, 1.0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn bilinear_interpolation() {
+        // 2 x 2 grid with two samples per A-Scan
+        let grid = array![[[0.0, 1.0], [1.0, 2.0]], [[2.0, 3.0], [3.0, 4.0]]];
+
+        assert_eq!(Interpolation::Bilinear.a_scan(grid.view(), 0.25, 0.5), array![1.25, 2.25]);
+        assert_eq!(Interpolation::Bilinear.a_scan(grid.view(), 1.0, 1.0), array![3.0, 4.0]);
+        assert_eq!(Interpolation::Bilinear.a_scan(grid.view(), 0.5, 0.0), array![0.5, 1.5]);
+        assert_eq!(Interpolation::Nearest.a_scan(grid.view(), 0.25, 0.5), array![2.0, 3.0]);
+    }
+
+    /// Creates a client for routes with a loaded dataset
+    fn test_client(data: UsData, routes: Vec<Route>) -> Client {
+        let handler = crate::DataHandler { dataset: Mutex::new(Some(data)), gates: Default::default(), corrections: Default::default(),
+            software_gains: Default::default(), voltage_ranges: Default::default(), material: Default::default(),
+            cache: Mutex::new(ScanCache::new(cache::MAX_CACHE_BYTES)), volumes: Default::default() };
+
+        Client::untracked(rocket::build().mount("/", routes).manage(handler)).unwrap()
+    }
+
+    #[test]
+    fn tile_voltage_range() {
+        let client = test_client(UsData::load_sonoware(synthetic_scan(4, 2, 64)).unwrap(),
+            rocket::routes![crate::get_c_scan_tile, crate::set_voltage_range]);
+        let tile = || {
+            let response = client.get("/c_scan/tile?c=0&start=5&end=40&as_decibel=0&unit=volts&level=0&tx=0&ty=0").dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            response.into_json::<serde_json::Value>().unwrap()["tile"][0][0].as_f64().unwrap()
+        };
+
+        assert_eq!(client.post("/voltage_range?c=0&range=1").dispatch().status(), Status::Ok);
+        let first = tile();
+        assert_eq!(client.post("/voltage_range?c=0&range=4").dispatch().status(), Status::Ok);
+        assert!((tile() - 4.0 * first).abs() < 1e-9);
+
+        assert_eq!(client.post("/voltage_range?c=0&range=0").dispatch().status(), Status::Ok);
+        assert_eq!(client.get("/c_scan/tile?c=0&start=5&end=40&as_decibel=0&unit=volts&level=0&tx=0&ty=0").dispatch().status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn gate_scans_velocity() {
+        let client = test_client(UsData::load_sonoware(synthetic_scan(4, 2, 128)).unwrap(), rocket::routes![crate::set_gates, crate::get_gate_scans]);
+        let response = client.post("/gates?c=0").header(ContentType::JSON)
+            .body(r#"{"gates": [{"name": "A", "start": 5, "end": 35, "method": "envelope_peak"}]}"#).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let d_scan = |query: &str| {
+            let response = client.get(format!("/gate_scans?c=0&as_decibel=0{}", query)).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            response.into_json::<serde_json::Value>().unwrap()["gates"][0]["d_scan"][0][1].as_f64().unwrap()
+        };
+
+        // without a selected material the depth requires the velocity of the request
+        assert_eq!(client.get("/gate_scans?c=0&as_decibel=0&depth=true").dispatch().status(), Status::BadRequest);
+        let time = d_scan("");
+        assert!((d_scan("&depth=true&velocity=5920") - DepthProfile::homogeneous(5920.0).depth(time)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_scan_batch() {
+        let data = UsData::load_sonoware(synthetic_scan(4, 2, 64)).unwrap();
+        let channels = data.channel_count();
+        let client = test_client(data, rocket::routes![crate::get_a_scan_batch, crate::get_channel_a_scans]);
+        let post = |body: String| client.post("/a_scan/batch?c=0").header(ContentType::JSON).body(body).dispatch();
+
+        let response = post(String::from(r#"{"positions": [[0, 0], [3, 1]], "start": 8, "end": 40, "max_points": 16, "average": true}"#));
+        assert_eq!(response.status(), Status::Ok);
+        let batch: serde_json::Value = response.into_json().unwrap();
+        let a_scans = batch["a_scans"].as_array().unwrap();
+        assert_eq!(a_scans.len(), 2);
+        assert!(batch["average"].is_object());
+
+        for a_scan in a_scans.iter().chain([&batch["average"]]) {
+            assert_eq!(a_scan["scan"].as_array().unwrap().len(), a_scan["scan_indices"].as_array().unwrap().len());
+            assert!(a_scan["filtered_scan"].as_array().unwrap().len() <= 16);
+            assert_eq!(a_scan["unit"], "normalized");
+            assert!((a_scan["time_start"].as_f64().unwrap() - 0.08).abs() < 1e-6);
+        }
+
+        let response = post(String::from(r#"{"positions": [[0, 0]]}"#));
+        let batch: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(batch["a_scans"][0]["scan"].as_array().unwrap().len(), 64);
+        assert!(batch.get("average").is_none());
+
+        // empty, too large or invalid batches are rejected
+        assert_eq!(post(String::from(r#"{"positions": []}"#)).status(), Status::BadRequest);
+        assert_eq!(post(String::from(r#"{"positions": [[4, 0]]}"#)).status(), Status::BadRequest);
+        let positions = vec![[0, 0]; crate::MAX_BATCH_POSITIONS + 1];
+        assert_eq!(post(serde_json::json!({ "positions": positions }).to_string()).status(), Status::BadRequest);
+
+        let response = client.get("/a_scan/channels?x=1&y=1&max_points=8").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let a_scans: Vec<serde_json::Value> = response.into_json().unwrap();
+        assert_eq!(a_scans.len(), channels);
+        assert!(a_scans.iter().all(|a_scan| a_scan["scan"].as_array().unwrap().len() <= 8));
+        assert_eq!(client.get("/a_scan/channels?x=1&y=2").dispatch().status(), Status::BadRequest);
+    }
+
+    /// Filtered volume of shape `[2, 3, 4]` with the value `100 * row + 10 * column + sample`,
+    /// the first column is negative and shifted by `40`
+    fn fixed_volume() -> FilteredVolume {
+        let filtered = Array3::from_shape_fn((2, 3, 4), |(row, col, sample)| match col {
+            0 => -((100 * row + 50 + sample) as f32),
+            _ => (100 * row + 10 * col + sample) as f32
+        });
+
+        FilteredVolume { filter_key: String::new(), filter: AScanFilter::load(), filtered, envelope: None }
+    }
+
+    #[test]
+    fn b_scan_orientation() {
+        let data = UsData::load_sonoware(synthetic_scan(3, 2, 4)).unwrap();
+        let volume = fixed_volume();
+        let samples = Samples::Filtered(&volume);
+
+        // along the second row, one A-Scan per column
+        let row = section::b_scan(&data, 0, ScanAxis::X, 1, samples, false).unwrap();
+        assert_eq!(row, array![[-150.0, -151.0, -152.0, -153.0], [110.0, 111.0, 112.0, 113.0], [120.0, 121.0, 122.0, 123.0]]);
+
+        // along the third column, one A-Scan per row
+        let col = section::b_scan(&data, 0, ScanAxis::Y, 2, samples, false).unwrap();
+        assert_eq!(col, array![[20.0, 21.0, 22.0, 23.0], [120.0, 121.0, 122.0, 123.0]]);
+
+        assert!(section::b_scan(&data, 0, ScanAxis::X, 2, samples, false).is_none());
+        assert!(section::b_scan(&data, 0, ScanAxis::Y, 3, samples, false).is_none());
+    }
+
+    #[test]
+    fn projection_maximum() {
+        let data = UsData::load_sonoware(synthetic_scan(3, 2, 4)).unwrap();
+        let volume = fixed_volume();
+        let samples = Samples::Filtered(&volume);
+
+        // side view: maximum magnitude over the columns of each row inside the time gate
+        let (first, side) = section::projection(&data, 0, ScanAxis::X, 1, 3, None, samples, false).unwrap();
+        assert_eq!(first, 0);
+        assert_eq!(side, array![[51.0, 52.0], [151.0, 152.0]]);
+
+        // end view: maximum magnitude over the rows of each column
+        let (first, end) = section::projection(&data, 0, ScanAxis::Y, 1, 3, None, samples, false).unwrap();
+        assert_eq!(first, 0);
+        assert_eq!(end, array![[151.0, 152.0], [111.0, 112.0], [121.0, 122.0]]);
+
+        // the maximum only covers the datapoints inside the mask
+        let mask = array![[true, true, true], [false, true, true]];
+        let (_, end) = section::projection(&data, 0, ScanAxis::Y, 0, 4, Some(&mask), samples, false).unwrap();
+        assert_eq!(end.row(0).to_vec(), vec![50.0, 51.0, 52.0, 53.0]);
+    }
+
+    #[test]
+    fn projection_roi() {
+        let data = UsData::load_sonoware(synthetic_scan(6, 3, 128)).unwrap();
+        let filter = AScanFilter::load();
+        let samples = Samples::Raw(&filter);
+
+        // a column of the region equals the end view of the complete scan
+        let column = Roi::Rect { x_start: 2.0, x_end: 2.0, y_start: 0.0, y_end: 2.0, unit: RoiUnit::Index }.resolve(3, 6, 0.5, 0.5).unwrap();
+        let (_, full) = section::projection(&data, 0, ScanAxis::Y, 10, 90, None, samples, false).unwrap();
+        let (first, scan) = section::projection(&data, 0, ScanAxis::Y, 10, 90, Some(&column.mask), samples, false).unwrap();
+        assert_eq!((first, scan.dim()), (2, (1, 80)));
+        assert_eq!(scan.row(0), full.row(2));
+
+        // positions without datapoints inside the region stay empty
+        let mask = Array2::from_shape_fn((3, 6), |(row, col)| row == 0 && (col == 1 || col == 4));
+        let (first, scan) = section::projection(&data, 0, ScanAxis::Y, 10, 90, Some(&mask), samples, false).unwrap();
+        assert_eq!((first, scan.nrows()), (1, 4));
+        assert!(scan.row(1).iter().all(|value| value.is_nan()));
+        assert!(scan.row(3).iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn dac_curve() {
+        let data = UsData::load_sonoware(synthetic_scan(6, 3, 128)).unwrap();
+        let subset = data.get_channel_subset(0).unwrap();
+        let channel = data.get_channel(0).unwrap();
+        let a_scan = AScanFilter::load().with_gain(6.0).apply(channel.slice(s![0, 0, ..]));
+        let level = |index: usize| 20.0 * a_scan[index].abs().log10();
+
+        // reference reflector at the interface echo, the second point lies between two samples
+        let reflector = (10..30).max_by(|a, b| a_scan[*a].abs().total_cmp(&a_scan[*b].abs())).unwrap();
+        let points = vec![
+            CurvePoint { time: subset.sample_time(reflector as f64), level: level(reflector) },
+            CurvePoint { time: subset.sample_time(reflector as f64 + 40.5), level: level(reflector) - 9.0 }
+        ];
+        let curve_level = |index: usize| level(reflector) - 9.0 * ((index - reflector) as f64 / 40.5).min(1.0);
+        let dac = CorrectionCurve { kind: CurveKind::Dac, points };
+        let filter = AScanFilter::load().with_gain(6.0).with_correction(&dac, subset, 128);
+
+        // dB values refer to the curve regardless of the requested reference
+        let reference = dac.decibel_reference(DecibelReference::User(0.1));
+        assert_eq!(reference, DecibelReference::Fsh);
+
+        let db = |start: usize, end: usize| {
+            let c_scan = data.c_scan(0, &Gate::absolute(start, end), Samples::Raw(&filter), AmplitudeMode::AbsPeak, None).unwrap();
+            reference.convert(&c_scan, AmplitudeMode::AbsPeak, subset.gain)[[0, 0]]
+        };
+
+        assert!(db(reflector, reflector + 1).abs() < 1e-5);
+
+        let expected = (50..70).map(|index| level(index) - curve_level(index)).fold(f64::NEG_INFINITY, f64::max);
+        assert!((db(50, 70) - expected).abs() < 1e-5);
+
+        // dB thresholds refer to the curve as well
+        let threshold = Threshold { value: -6.0, unit: ThresholdUnit::Decibel, ..Threshold::default() };
+        assert!((threshold.linear(subset.gain, true) - f64::powf(10.0, -0.3)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn decibel_reference() {
+        let full_scale = f64::powi(2.0, 15) - 1.0;
+        let raw = DecibelReference::Raw.scale(AmplitudeMode::Peak, 10.0, || f64::NAN);
+        assert!((raw.apply(0.5) - (20.0 * (0.5 * full_scale).log10() - 10.0)).abs() < 1e-9);
+        assert_eq!(raw.apply(0.0), -10.0);
+        assert!(raw.apply(f64::NAN).is_nan());
+
+        let energy = DecibelReference::Raw.scale(AmplitudeMode::Energy, 0.0, || f64::NAN);
+        assert!((energy.apply(0.25) - 10.0 * (0.25 * full_scale * full_scale).log10()).abs() < 1e-9);
+
+        let fsh = DecibelReference::Fsh.scale(AmplitudeMode::Peak, 10.0, || f64::NAN);
+        assert_eq!(fsh.apply(1.0), 0.0);
+        assert!((fsh.apply(0.1) + 20.0).abs() < 1e-9);
+
+        let scan = array![[0.5, 0.25], [f64::NAN, 0.0]];
+        let scan_max = DecibelReference::ScanMax.convert(&scan, AmplitudeMode::Peak, 0.0);
+        assert_eq!(scan_max[[0, 0]], 0.0);
+        assert!((scan_max[[0, 1]] + 20.0 * 2f64.log10()).abs() < 1e-9);
+        assert!(scan_max[[1, 0]].is_nan());
+        assert!(scan_max[[1, 1]].is_finite());
+
+        let user = DecibelReference::from_request(Some(DecibelReferenceKind::User), Some(0.25)).unwrap();
+        assert!((user.convert(&scan, AmplitudeMode::Peak, 0.0)[[0, 0]] - 20.0 * 2f64.log10()).abs() < 1e-9);
+        assert!(DecibelReference::from_request(Some(DecibelReferenceKind::User), None).is_err());
+        assert!(DecibelReference::from_request(Some(DecibelReferenceKind::User), Some(0.0)).is_err());
+        assert_eq!(DecibelReference::from_request(None, None), Ok(DecibelReference::Raw));
+
+        // dB thresholds are the inverse of the conversion of their reference
+        let threshold = Threshold { value: 40.0, unit: ThresholdUnit::Decibel, ..Threshold::default() };
+        assert!((raw.apply(threshold.linear(10.0, false)) - 40.0).abs() < 1e-9);
+
+        let threshold = Threshold { value: -6.0, unit: ThresholdUnit::Decibel, db_ref: DecibelReferenceKind::User, db_value: Some(0.5) };
+        assert!(threshold.validate().is_ok());
+        assert!((threshold.linear(10.0, false) - 0.5 * f64::powf(10.0, -0.3)).abs() < 1e-9);
+        assert!((Threshold { db_ref: DecibelReferenceKind::Fsh, ..threshold }.linear(10.0, false) - f64::powf(10.0, -0.3)).abs() < 1e-9);
+        assert!(Threshold { db_value: None, ..threshold }.validate().is_err());
+        assert!(Threshold { db_ref: DecibelReferenceKind::ScanMax, ..threshold }.validate().is_err());
+        assert!(Threshold { db_ref: DecibelReferenceKind::ScanMax, unit: ThresholdUnit::Percent, ..threshold }.validate().is_ok());
+    }
+
+    #[test]
+    fn amplitude_units() {
+        let counts = AmplitudeUnit::Counts.scale(None).unwrap();
+
+        assert_eq!(counts, amplitude::COUNTS);
+        assert_eq!((counts.normalize(i16::MIN as f64), counts.normalize(i16::MAX as f64)), (-1.0, 1.0));
+
+        for count in [i16::MIN, -1, 0, 1, 1000, i16::MAX] {
+            assert!((counts.sample(counts.normalize(count as f64)) - count as f64).abs() < 1e-9);
+        }
+
+        // the loaded samples are converted back into integral counts
+        let data = UsData::load_sonoware(synthetic_scan(2, 1, 128)).unwrap();
+        assert!(data.get_channel(0).unwrap().iter().all(|sample| (counts.sample(*sample) - counts.sample(*sample).round()).abs() < 1e-6));
+
+        let fsh = AmplitudeUnit::Fsh.scale(None).unwrap();
+        assert_eq!(fsh.amplitude(0.5, AmplitudeMode::Peak), 50.0);
+        assert_eq!(fsh.amplitude(0.25, AmplitudeMode::Energy), 2500.0);
+
+        assert!(AmplitudeUnit::Volts.scale(None).is_err());
+        assert_eq!(AmplitudeUnit::Volts.scale(Some(2.0)).unwrap().convert(&array![[0.5, f64::NAN]], AmplitudeMode::Peak)[[0, 0]], 1.0);
+        assert_eq!(AmplitudeUnit::Normalized.scale(None).unwrap().sample(-0.5), -0.5);
+    }
+
+    #[test]
+    fn gate_validation() {
+        assert!(Gate::absolute(10, 20).validate(128).is_ok());
+        assert!(Gate::absolute(20, 20).validate(128).is_err());
+        assert!(Gate::absolute(100, 200).validate(128).is_err());
+
+        let interface = InterfaceGate { start: 0, end: 200, threshold: Default::default() };
+        assert!(Gate { start: 0, end: 10, interface: Some(interface) }.validate(128).is_err());
+
+        let a_scan = [0.0, 1.0, 0.5, 0.25];
+        let mut scratch = vec![];
+        assert_eq!(Gate::absolute(1, 100).window(&a_scan, 0.0, &AScanFilter::load(), &mut scratch), Some((1, 4)));
+        assert_eq!(Gate::absolute(4, 100).window(&a_scan, 0.0, &AScanFilter::load(), &mut scratch), None);
+    }
+
+    #[test]
+    fn interface_gate() {
+        let echo = |position: usize, amplitude: f64| {
+            let mut a_scan = vec![0.0; 64];
+            a_scan[position] = amplitude;
+            a_scan
+        };
+        let filter = AScanFilter::load();
+        let mut scratch = vec![];
+
+        let interface = InterfaceGate { start: 5, end: 60, threshold: Threshold::default() };
+        let gate = Gate { start: 10, end: 20, interface: Some(interface) };
+
+        assert_eq!(interface.detect(&echo(12, 0.8), 0.0, &filter, &mut scratch), Some(12));
+        assert_eq!(gate.window(&echo(12, 0.8), 0.0, &filter, &mut scratch), Some((22, 32)));
+        assert_eq!(gate.window(&echo(18, -0.8), 0.0, &filter, &mut scratch), Some((28, 38)));
+
+        // below the threshold, outside of the search range or only exceeded due to the software gain
+        assert_eq!(interface.detect(&echo(12, 0.3), 0.0, &filter, &mut scratch), None);
+        assert_eq!(gate.window(&echo(12, 0.3), 0.0, &filter, &mut scratch), None);
+        assert_eq!(gate.window(&echo(62, 0.8), 0.0, &filter, &mut scratch), None);
+        assert_eq!(gate.window(&echo(12, 0.8), 0.0, &filter.clone().with_gain(6.0), &mut scratch), None);
+
+        // clipped at the end of the A-Scan
+        assert_eq!(gate.window(&echo(50, 0.8), 0.0, &filter, &mut scratch), Some((60, 64)));
+        assert_eq!(Gate { start: 20, ..gate }.window(&echo(50, 0.8), 0.0, &filter, &mut scratch), None);
+
+        let long_search = InterfaceGate { end: 200, ..interface };
+        assert_eq!(long_search.detect(&echo(63, 0.8), 0.0, &filter, &mut scratch), Some(63));
+    }
+
+    #[test]
+    fn named_gates() {
+        let config = |json: &str| serde_json::from_str::<GateConfig>(json).unwrap();
+
+        let valid = config(r#"{"gates": [{"name": "A", "start": 5, "end": 35}, {"name": "B_2", "start": 45, "end": 80}],
+            "differences": [{"from": "A", "to": "B_2"}]}"#);
+        assert!(valid.validate().is_ok());
+        assert_eq!(valid.differences[0].name(), "B_2-A");
+
+        assert!(config(r#"{"gates": [{"name": "A", "start": 5, "end": 35}, {"name": "A", "start": 45, "end": 80}]}"#).validate().is_err());
+        assert!(config(r#"{"gates": [{"name": "A-1", "start": 5, "end": 35}]}"#).validate().is_err());
+        assert!(config(r#"{"gates": [{"name": "", "start": 5, "end": 35}]}"#).validate().is_err());
+        assert!(config(r#"{"gates": [{"name": "A", "start": 5, "end": 35}], "differences": [{"from": "A", "to": "C"}]}"#).validate().is_err());
+        assert!(config(r#"{"gates": [{"name": "A", "start": 5, "end": 35,
+            "threshold": {"value": -6.0, "unit": "decibel", "db_ref": "scan_max"}}]}"#).validate().is_err());
+
+        // the back-wall echo of the synthetic scan follows the interface echo by 40 samples (0.4 µs),
+        // the envelope of the filtered echoes is slightly distorted at the gate borders
+        let data = UsData::load_sonoware(synthetic_scan(4, 2, 128)).unwrap();
+        let filter = AScanFilter::load();
+        let method = r#""method": "envelope_peak""#;
+        let config = config(&format!(r#"{{"gates": [{{"name": "A", "start": 5, "end": 35, {0}}}, {{"name": "B", "start": 45, "end": 80, {0}}}],
+            "differences": [{{"from": "A", "to": "B"}}, {{"from": "B", "to": "A"}}]}}"#, method));
+        assert!(config.validate().is_ok());
+
+        let scans = data.gate_scans(0, &config.gates, Samples::Raw(&filter), None).unwrap();
+        let differences = crate::gate_differences(&config, &scans);
+
+        assert_eq!(differences[0].0, "B-A");
+        assert_eq!(differences[0].1, &scans[1].time - &scans[0].time);
+        assert!(differences[0].1.iter().all(|difference| (0.35..0.6).contains(difference)));
+        assert_eq!(differences[1].0, "A-B");
+        assert_eq!(differences[1].1, -&differences[0].1);
+    }
+
+    #[test]
+    fn raw_and_volume_scans() {
+        let data = UsData::load_sonoware(synthetic_scan(6, 3, 128)).unwrap();
+        let subset = data.get_channel_subset(0).unwrap();
+        let curve = CorrectionCurve { kind: CurveKind::Tcg, points: vec![CurvePoint { time: 0.0, level: 0.0 }, CurvePoint { time: 1.2, level: 12.0 }] };
+        let filter = AScanFilter::load().with_gain(6.0).with_correction(&curve, subset, 128);
+
+        let store = Arc::new(Mutex::new(VolumeStore::default()));
+        store.lock().unwrap().start(&store, 0, data.get_channel(0).unwrap().clone(), filter.clone(), false);
+
+        let mut volume = None;
+        for _ in 0..1000 {
+            volume = store.lock().unwrap().volume(0, &filter.key());
+            if volume.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let (raw, filtered) = (Samples::Raw(&filter), Samples::Filtered(volume.as_deref().unwrap()));
+        let same = |a: &Array2<f64>, b: &Array2<f64>| a.iter().zip(b).all(|(a, b)| a == b || (a.is_nan() && b.is_nan()));
+
+        let interface = InterfaceGate { start: 5, end: 60, threshold: Threshold { value: 1.0, ..Threshold::default() } };
+        let threshold = Threshold { value: 2.0, ..Threshold::default() };
+
+        for gate in [Gate::absolute(10, 50), Gate { start: 30, end: 60, interface: Some(interface) }] {
+            let c_scan = data.c_scan(0, &gate, raw, AmplitudeMode::Peak, None).unwrap();
+            assert!(c_scan.iter().any(|value| value.is_finite()));
+            assert!(same(&c_scan, &data.c_scan(0, &gate, filtered, AmplitudeMode::Peak, None).unwrap()));
+
+            for method in [TofMethod::Peak, TofMethod::Threshold] {
+                assert!(same(&data.d_scan(0, &gate, raw, method, threshold, None).unwrap(), &data.d_scan(0, &gate, filtered, method, threshold, None).unwrap()));
+            }
+        }
+
+        let slices = TimeSlices { start: 10, end: 70, width: 20, step: 20 };
+        assert_eq!(data.c_scan_stack(0, &slices, raw, None, AmplitudeMode::Peak), data.c_scan_stack(0, &slices, filtered, None, AmplitudeMode::Peak));
+
+        // a single window of the stack equals the C-Scan of the same gate
+        let stack = data.c_scan_stack(0, &slices, raw, None, AmplitudeMode::Peak).unwrap();
+        let c_scan = data.c_scan(0, &Gate::absolute(10, 30), raw, AmplitudeMode::Peak, None).unwrap();
+        assert_eq!(stack.index_axis(Axis(0), 0), c_scan.mapv(|value| value as f32));
+    }
+
+    /// Builds a SonoWare file with an interface echo and a backwall echo
+    /// moving with the column
+    fn synthetic_scan(cols: usize, rows: usize, samples: usize) -> Vec<u8> {
+        synthetic_scan_at(cols, rows, samples, 0.0)
+    }
+
+    /// Builds the synthetic SonoWare file of `synthetic_scan` with A-Scans starting at `start`
+    /// (the header value, the parser divides a start given in `us` by 1000)
+    fn synthetic_scan_at(cols: usize, rows: usize, samples: usize, start: f64) -> Vec<u8> {
+        let mut header = format!("Format: SDT\nVersion: 1\n-\nAxes: 2\nSubsets: 2\n-\nX: {}\n-\nResX: 0.5 mm\n-\nY: {}\n-\nResY: 0.5 mm\n-\n", cols, rows);
+
+        for (name, count) in [("Data 1", samples), ("Time", 1)] {
+            header += &format!("Name: {}\nSize: 2\n-\nSamples: {}\nStart: {:.3} us\nRes: 10.000 ns\n-\n-\n-\n-\n-\n-\n", name, count, start);
+        }
+
+        let mut bytes = format!("{}<\"Gain\">6 |^Data Set^|\r\n\0", header).into_bytes();
+
+        for row in 0..rows {
+            for col in 0..cols {
+                for index in 0..samples {
+                    let echo = |center: f64, amplitude: f64| {
+                        let t = index as f64 - center;
+                        amplitude * (-t * t / 4.0).exp() * (1.2 * t).cos()
+                    };
+                    let interface = 20.0 + col as f64 * 0.5 + row as f64 * 0.2;
+                    let value = echo(interface, 0.8) + echo(interface + 40.0, -0.4);
+
+                    bytes.extend((((value + 1.0) / 2.0 * 65535.0 - 32768.0).round() as i16).to_be_bytes());
+                }
+            }
+        }
+
+        bytes.extend(vec![0; 2 * cols * rows]);
+        bytes
+    }
+
+    #[test]
+    fn material_depth() {
+        let library = MaterialLibrary::load().unwrap();
+        assert!(library.get("steel").is_some());
+
+        let homogeneous = DepthProfile::homogeneous(5920.0);
+        assert!((homogeneous.depth(10.0) - time_to_thickness(10.0, 5920.0)).abs() < 1e-9);
+        assert!(homogeneous.depth(f64::NAN).is_nan());
+
+        let layers = vec![
+            Layer { material: String::from("water"), thickness: Some(14.8) },
+            Layer { material: String::from("steel"), thickness: None }
+        ];
+        let profile = MaterialStack { layers: layers.clone(), wave: WaveMode::Longitudinal }.resolve(&library).unwrap();
+        assert!((profile.depth(20.0) - 14.8).abs() < 1e-9);
+        assert!((profile.depth(30.0) - (14.8 + time_to_thickness(10.0, 5920.0))).abs() < 1e-9);
+
+        for depth in [5.0, 14.8, 30.0] {
+            assert!((profile.depth(profile.time(depth)) - depth).abs() < 1e-9);
+        }
+
+        assert!(MaterialStack { layers: layers.clone(), wave: WaveMode::Transverse }.resolve(&library).is_err());
+        assert!(MaterialStack { layers: vec![Layer { material: String::from("unobtainium"), thickness: None }], wave: WaveMode::Longitudinal }
+            .resolve(&library).is_err());
+        assert!(MaterialStack { layers: layers.into_iter().rev().collect(), wave: WaveMode::Longitudinal }.resolve(&library).is_err());
+    }
+
+    #[test]
+    fn velocity_calibration() {
+        let region = |x: f64, thickness: f64| CalibrationRegion {
+            roi: Roi::Rect { x_start: x, x_end: x + 1.0, y_start: 0.0, y_end: 1.0, unit: RoiUnit::Index },
+            thickness,
+            uncertainty: 0.0
+        };
+        let masks: Vec<_> = [0.0, 2.0].iter().map(|x| region(*x, 1.0).roi.resolve(2, 4, 1.0, 1.0).unwrap().mask).collect();
+
+        // steps of 5 and 10 mm in steel with a time offset of 1 µs
+        let time_of_flight = array![[2.6891891891891895, 2.6891891891891895, 4.378378378378379, f64::NAN], [2.6891891891891895, 2.6891891891891895, 4.378378378378379, 4.378378378378379]];
+        let mut calibration = VelocityCalibration { gate: String::from("B"), reference: None, regions: vec![region(0.0, 5.0), region(2.0, 10.0)],
+            wave: WaveMode::Longitudinal, save: None };
+
+        let result = calibration.estimate(&time_of_flight, &masks, 0.0).unwrap();
+        assert!((result.velocity - 5920.0).abs() < 1e-6);
+        assert!(result.uncertainty.abs() < 1e-6);
+        assert!((result.offset.unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(result.regions[1].count, 3);
+
+        // a single region attributes the offset to the material and therefore requires a reference
+        let gates: GateConfig = serde_json::from_str(r#"{"gates": [{"name": "A", "start": 5, "end": 35}, {"name": "B", "start": 45, "end": 80}]}"#).unwrap();
+        assert!(calibration.validate(&gates).is_ok());
+        calibration.regions.truncate(1);
+        assert!(calibration.validate(&gates).is_err());
+        assert!(VelocityCalibration { reference: Some(String::from("A")), ..calibration.clone() }.validate(&gates).is_ok());
+
+        let result = calibration.estimate(&time_of_flight, &masks[..1], 0.01).unwrap();
+        assert!(result.velocity < 5920.0 && result.offset.is_none());
+        assert!(result.uncertainty > 0.0);
+
+        assert!(calibration.estimate(&time_of_flight.mapv(|_| f64::NAN), &masks[..1], 0.01).is_err());
+
+        // the start time of the A-Scans cancels with the interface echo as reference,
+        // the backwall echo of the synthetic scan follows by 0.4 µs
+        let data = UsData::load_sonoware(synthetic_scan_at(4, 2, 128, 5000.0)).unwrap();
+        assert_eq!(data.get_channel_subset(0).unwrap().min_sample_pos, 5.0);
+        let gates: GateConfig = serde_json::from_str(r#"{"gates": [{"name": "A", "start": 5, "end": 35, "method": "envelope_peak"},
+            {"name": "B", "start": 45, "end": 80, "method": "envelope_peak"}]}"#).unwrap();
+        let scans = data.gate_scans(0, &gates.gates, Samples::Raw(&AScanFilter::load()), None).unwrap();
+        let row = Roi::Rect { x_start: 0.0, x_end: 3.0, y_start: 0.0, y_end: 0.0, unit: RoiUnit::Index }.resolve(2, 4, 0.5, 0.5).unwrap();
+        let calibration = VelocityCalibration { gate: String::from("B"), reference: Some(String::from("A")), regions: vec![region(0.0, 1.184)],
+            wave: WaveMode::Longitudinal, save: None };
+        assert!(calibration.validate(&gates).is_ok());
+
+        let time_of_flight = calibration.time_of_flight(&gates, &scans);
+        assert!(scans[1].time.iter().all(|time| *time > 5.0));
+        let result = calibration.estimate(&time_of_flight, std::slice::from_ref(&row.mask), 0.01).unwrap();
+        assert!((result.velocity - 5920.0).abs() < 1.0);
+        assert!(calibration.estimate(&scans[1].time, &[row.mask], 0.01).unwrap().velocity < 1000.0);
+
+        let mut library = MaterialLibrary::default();
+        assert!(library.set_velocity("test", 3000.0, WaveMode::Transverse).is_err());
+        library.set_velocity("test", 6000.0, WaveMode::Longitudinal).unwrap();
+        assert_eq!(library.set_velocity("test", 3000.0, WaveMode::Transverse).unwrap().transverse_velocity, Some(3000.0));
+        assert_eq!(library.get("test").unwrap().longitudinal_velocity, 6000.0);
+    }
+
     fn run_test_on(ref_path: &str, x: usize, y: usize) {
         let file = fs::read_to_string(ref_path).unwrap();
 
@@ -55,7 +865,7 @@ mod tests {
         }
     }
 
-    fn check_scan(calc: UsData, reference: &Vec<i16>, x: usize, y: usize) {
+    fn check_scan(calc: UsData, reference: &[i16], x: usize, y: usize) {
         let a_scan = calc.get_channel(0).unwrap();
         let start = a_scan.slice(s![x, y, ..]);
 
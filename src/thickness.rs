@@ -0,0 +1,73 @@
+use ndarray::{ArrayBase, OwnedRepr, Dim};
+use rocket::FromForm;
+use serde::{Serialize, Deserialize};
+
+use crate::data::GateScan;
+use crate::gate::GateConfig;
+
+/// Configuration of a wall thickness measurement
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromForm)]
+pub struct ThicknessConfig {
+    /// Sound velocity of the material in m/s
+    pub velocity: f64,
+    /// Name of the gate measuring the backwall echo
+    pub gate: String,
+    /// Optional name of the reference gate, e.g. the interface gate or the
+    /// gate of the first backwall echo for echo-to-echo measurements
+    pub reference: Option<String>
+}
+
+impl ThicknessConfig {
+    /// Checks if the configuration fits to the gates of a channel
+    ///
+    /// # Arguments
+    /// * `gates`: Gate configuration of the channel
+    ///
+    /// # Errors
+    /// A message is returned if the velocity isn't positive or a gate is unknown
+    pub fn validate(&self, gates: &GateConfig) -> Result<(), String> {
+        if self.velocity.is_nan() || self.velocity <= 0.0 {
+            return Err(String::from("The sound velocity has to be positive!"));
+        }
+
+        for name in std::iter::once(&self.gate).chain(self.reference.iter()) {
+            if gates.index_of(name).is_none() {
+                return Err(format!("Unknown gate {}!", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calculates the wall thickness map
+    ///
+    /// # Arguments
+    /// * `gates`: Gate configuration of the channel
+    /// * `scans`: Scans of the gates in the order of `gates.gates`
+    ///
+    /// # Returns
+    /// A 2-D-Array containing the wall thickness in mm. Without a reference gate
+    /// the time of flight since the start of the A-Scan is used.
+    pub fn thickness(&self, gates: &GateConfig, scans: &[GateScan]) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        let time = &scans[gates.index_of(&self.gate).unwrap()].time;
+
+        let time_of_flight = match &self.reference {
+            Some(reference) => time - &scans[gates.index_of(reference).unwrap()].time,
+            None => time.clone()
+        };
+
+        time_of_flight.mapv(|time| time_to_thickness(time, self.velocity))
+    }
+}
+
+/// Converts a pulse-echo time of flight into a thickness
+///
+/// # Arguments
+/// * `time`: Time of flight in µs
+/// * `velocity`: Sound velocity in m/s
+///
+/// # Returns
+/// The thickness in mm
+pub fn time_to_thickness(time: f64, velocity: f64) -> f64 {
+    velocity * time / 1000.0 / 2.0
+}
@@ -0,0 +1,84 @@
+use ndarray::{Array, Array2, s};
+use rocket::FromFormField;
+use serde::Serialize;
+
+/// Number of datapoints along each side of a tile
+pub const TILE_SIZE: usize = 256;
+
+/// Aggregation of the datapoints combined in a coarser level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum TileAggregation {
+    /// Maximum value
+    #[default]
+    #[field(value = "max")]
+    Max,
+    /// Mean value
+    #[field(value = "mean")]
+    Mean
+}
+
+/// Returns the number of levels of the pyramid of a scan
+///
+/// # Arguments
+/// * `rows`: Number of rows at full resolution
+/// * `cols`: Number of columns at full resolution
+///
+/// # Returns
+/// The number of levels, the last level fits into a single tile
+pub fn levels(rows: usize, cols: usize) -> usize {
+    let mut levels = 1;
+
+    while rows.max(cols).div_ceil(1 << (levels - 1)) > TILE_SIZE {
+        levels += 1;
+    }
+
+    levels
+}
+
+/// Reduces the resolution of a scan
+///
+/// # Arguments
+/// * `scan`: Scan at full resolution
+/// * `factor`: Number of datapoints combined along each axis
+/// * `aggregation`: Aggregation of the combined datapoints
+///
+/// # Returns
+/// The scan with `factor` times fewer rows and columns (rounded up).
+/// `NaN` values are ignored, blocks without valid values are `NaN`.
+pub fn downsample(scan: &Array2<f64>, factor: usize, aggregation: TileAggregation) -> Array2<f64> {
+    let (rows, cols) = scan.dim();
+
+    Array::from_shape_fn((rows.div_ceil(factor), cols.div_ceil(factor)), |(row, col)| {
+        let block = scan.slice(s![row * factor..((row + 1) * factor).min(rows), col * factor..((col + 1) * factor).min(cols)]);
+        let (count, sum, max) = block.iter().filter(|value| !value.is_nan())
+            .fold((0, 0.0, f64::NEG_INFINITY), |(count, sum, max), &value| (count + 1, sum + value, max.max(value)));
+
+        match (count, aggregation) {
+            (0, _) => f64::NAN,
+            (_, TileAggregation::Max) => max,
+            (_, TileAggregation::Mean) => sum / count as f64
+        }
+    })
+}
+
+/// Extracts a tile of a level
+///
+/// # Arguments
+/// * `level_scan`: Scan of the level
+/// * `tx`: Column index of the tile
+/// * `ty`: Row index of the tile
+///
+/// # Returns
+/// The tile, which is smaller than `TILE_SIZE` at the right and bottom border,
+/// or **None** if the tile is outside of the scan
+pub fn tile(level_scan: &Array2<f64>, tx: usize, ty: usize) -> Option<Array2<f64>> {
+    let (rows, cols) = level_scan.dim();
+    let (row_start, col_start) = (ty.checked_mul(TILE_SIZE)?, tx.checked_mul(TILE_SIZE)?);
+
+    if row_start >= rows || col_start >= cols {
+        return None;
+    }
+
+    Some(level_scan.slice(s![row_start..(row_start + TILE_SIZE).min(rows), col_start..(col_start + TILE_SIZE).min(cols)]).to_owned())
+}
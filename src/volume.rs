@@ -0,0 +1,282 @@
+use std::{collections::HashMap, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}}, thread};
+use ndarray::{Array, ArrayBase, Axis, Dim, OwnedRepr, s};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::data::AScanFilter;
+use crate::signal::envelope;
+
+/// Filtered copy of a channel stored with single precision
+///
+/// Each A-Scan is filtered completely like the on demand filtering of the scans,
+/// the volume only stores the result to avoid filtering it again.
+pub struct FilteredVolume {
+    /// Key of the filter the volume has been computed with
+    pub filter_key: String,
+    /// Filter the volume has been computed with
+    pub filter: AScanFilter,
+    /// Filtered A-Scans of shape `[rows, columns, samples]`
+    pub filtered: ArrayBase<OwnedRepr<f32>, Dim<[usize; 3]>>,
+    /// Envelope of the filtered A-Scans if it has been requested
+    pub envelope: Option<ArrayBase<OwnedRepr<f32>, Dim<[usize; 3]>>>
+}
+
+impl FilteredVolume {
+    /// Computes the filtered volume of a channel
+    ///
+    /// # Arguments
+    /// * `data`: Samples of the channel
+    /// * `filter`: Filter applied to the A-Scans
+    /// * `job`: Job receiving the progress
+    ///
+    /// # Returns
+    /// The filtered volume or **None** if the job has been cancelled
+    fn compute(data: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 3]>>, filter: &AScanFilter, job: &VolumeJob) -> Option<FilteredVolume> {
+        let mut filtered = Array::zeros(data.dim());
+
+        filtered.axis_iter_mut(Axis(0)).into_par_iter().zip(data.axis_iter(Axis(0)).into_par_iter())
+            .for_each(|(mut filtered_row, row)| {
+                if job.cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let mut buffer = vec![];
+
+                for (mut filtered_a_scan, a_scan) in filtered_row.outer_iter_mut().zip(row.outer_iter()) {
+                    filter.apply_to(a_scan, &mut buffer);
+                    filtered_a_scan.iter_mut().zip(&buffer).for_each(|(value, sample)| *value = *sample as f32);
+                }
+
+                job.done.fetch_add(1, Ordering::Relaxed);
+            });
+
+        let envelope = if job.envelope {
+            let mut envelope_volume = Array::zeros(data.dim());
+
+            envelope_volume.axis_iter_mut(Axis(0)).into_par_iter().zip(filtered.axis_iter(Axis(0)).into_par_iter())
+                .for_each(|(mut envelope_row, row)| {
+                    if job.cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    for (mut envelope_a_scan, a_scan) in envelope_row.outer_iter_mut().zip(row.outer_iter()) {
+                        let samples: Vec<f64> = a_scan.iter().map(|sample| *sample as f64).collect();
+                        envelope_a_scan.iter_mut().zip(envelope(&samples)).for_each(|(value, sample)| *value = sample as f32);
+                    }
+
+                    job.done.fetch_add(1, Ordering::Relaxed);
+                });
+
+            Some(envelope_volume)
+        }
+        else {
+            None
+        };
+
+        if job.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        Some(FilteredVolume { filter_key: job.filter_key.clone(), filter: filter.clone(), filtered, envelope })
+    }
+
+    /// Returns a filtered A-Scan or its envelope
+    ///
+    /// # Arguments
+    /// * `row`: Row index
+    /// * `col`: Column index
+    /// * `as_envelope`: The envelope should be returned instead of the filtered A-Scan
+    ///
+    /// # Returns
+    /// The requested A-Scan. The envelope is calculated if it hasn't been precomputed.
+    pub fn a_scan(&self, row: usize, col: usize, as_envelope: bool) -> Vec<f64> {
+        let filtered: Vec<f64> = self.filtered.slice(s![row, col, ..]).iter().map(|sample| *sample as f64).collect();
+
+        match (as_envelope, &self.envelope) {
+            (false, _) => filtered,
+            (true, Some(volume)) => volume.slice(s![row, col, ..]).iter().map(|sample| *sample as f64).collect(),
+            (true, None) => envelope(&filtered)
+        }
+    }
+}
+
+/// Background computation of a filtered volume
+pub struct VolumeJob {
+    /// The envelope is computed in addition to the filtered A-Scans
+    envelope: bool,
+    /// Key of the filter applied by the job
+    filter_key: String,
+    /// Number of processed rows
+    done: AtomicUsize,
+    /// Number of rows to process, twice the rows of the channel if the envelope is computed
+    total: usize,
+    /// The result isn't needed anymore
+    cancelled: AtomicBool
+}
+
+/// State of the filtered volume of a channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeState {
+    /// No volume has been requested
+    None,
+    /// The volume is being computed
+    Running,
+    /// The volume is used for all scans
+    Ready,
+    /// The volume has been computed with a different filter
+    Stale
+}
+
+/// Status of the filtered volume of a channel
+#[derive(Serialize)]
+pub struct VolumeStatus {
+    /// Current state
+    pub state: VolumeState,
+    /// Progress of a running computation between `0` and `1`
+    pub progress: f64,
+    /// The envelope is precomputed as well
+    pub envelope: bool
+}
+
+/// Filtered volumes of the loaded dataset
+#[derive(Default)]
+pub struct VolumeStore {
+    /// Finished volumes of each channel
+    volumes: HashMap<usize, Arc<FilteredVolume>>,
+    /// Running jobs of each channel
+    jobs: HashMap<usize, Arc<VolumeJob>>,
+    /// Channels which should be kept precomputed with the envelope flag
+    requested: HashMap<usize, bool>
+}
+
+impl VolumeStore {
+    /// Starts the computation of a filtered volume in the background
+    ///
+    /// A running job of the channel is cancelled.
+    ///
+    /// # Arguments
+    /// * `shared`: Shared handle of this store receiving the result
+    /// * `channel`: Channel index
+    /// * `data`: Copy of the channel samples
+    /// * `filter`: Filter applied to the A-Scans
+    /// * `with_envelope`: The envelope should be computed as well
+    pub fn start(&mut self, shared: &Arc<Mutex<VolumeStore>>, channel: usize, data: ArrayBase<OwnedRepr<f64>, Dim<[usize; 3]>>,
+        filter: AScanFilter, with_envelope: bool) {
+        self.cancel(channel);
+        self.requested.insert(channel, with_envelope);
+
+        let job = Arc::new(VolumeJob {
+            envelope: with_envelope,
+            filter_key: filter.key(),
+            done: AtomicUsize::new(0),
+            total: data.shape()[0] * if with_envelope { 2 } else { 1 },
+            cancelled: AtomicBool::new(false)
+        });
+
+        self.jobs.insert(channel, job.clone());
+
+        let shared = shared.clone();
+
+        thread::spawn(move || {
+            let volume = FilteredVolume::compute(&data, &filter, &job);
+
+            if let Ok(mut store) = shared.lock() {
+                // a newer job or a reset replaces this one
+                if store.jobs.get(&channel).is_some_and(|current| Arc::ptr_eq(current, &job)) {
+                    store.jobs.remove(&channel);
+
+                    if let Some(volume) = volume {
+                        store.volumes.insert(channel, Arc::new(volume));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Cancels the running job of a channel
+    fn cancel(&mut self, channel: usize) {
+        if let Some(job) = self.jobs.remove(&channel) {
+            job.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the volume of a channel if it has been computed with the current filter
+    ///
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// * `filter_key`: Key of the current filter
+    pub fn volume(&self, channel: usize, filter_key: &str) -> Option<Arc<FilteredVolume>> {
+        self.volumes.get(&channel).filter(|volume| volume.filter_key == filter_key).cloned()
+    }
+
+    /// Checks if the volume of a requested channel has to be (re)computed
+    ///
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// * `filter_key`: Key of the current filter
+    ///
+    /// # Returns
+    /// The envelope flag of the request if the volume is missing or outdated
+    /// and no job with the current filter is running, else **None**
+    pub fn needs_update(&self, channel: usize, filter_key: &str) -> Option<bool> {
+        let with_envelope = *self.requested.get(&channel)?;
+        let running = self.jobs.get(&channel).is_some_and(|job| job.filter_key == filter_key);
+
+        if running || self.volume(channel, filter_key).is_some() {
+            None
+        }
+        else {
+            Some(with_envelope)
+        }
+    }
+
+    /// Returns the status of the volume of a channel
+    ///
+    /// # Arguments
+    /// * `channel`: Channel index
+    /// * `filter_key`: Key of the current filter
+    pub fn status(&self, channel: usize, filter_key: &str) -> VolumeStatus {
+        let with_envelope = self.requested.get(&channel).copied().unwrap_or(false);
+
+        if let Some(job) = self.jobs.get(&channel) {
+            return VolumeStatus {
+                state: VolumeState::Running,
+                progress: job.done.load(Ordering::Relaxed) as f64 / job.total.max(1) as f64,
+                envelope: job.envelope
+            };
+        }
+
+        match self.volumes.get(&channel) {
+            Some(volume) if volume.filter_key == filter_key => VolumeStatus { state: VolumeState::Ready, progress: 1.0, envelope: volume.envelope.is_some() },
+            Some(_) => VolumeStatus { state: VolumeState::Stale, progress: 0.0, envelope: with_envelope },
+            None => VolumeStatus { state: VolumeState::None, progress: 0.0, envelope: with_envelope }
+        }
+    }
+
+    /// Returns the channels which should be kept precomputed
+    pub fn requested_channels(&self) -> Vec<usize> {
+        self.requested.keys().copied().collect()
+    }
+
+    /// Removes the volume and the request of a channel
+    ///
+    /// # Arguments
+    /// * `channel`: Channel index
+    pub fn remove(&mut self, channel: usize) {
+        self.cancel(channel);
+        self.volumes.remove(&channel);
+        self.requested.remove(&channel);
+    }
+
+    /// Drops all volumes and jobs of the previous dataset while keeping the requests
+    pub fn reset(&mut self) {
+        let channels: Vec<usize> = self.jobs.keys().copied().collect();
+
+        for channel in channels {
+            self.cancel(channel);
+        }
+
+        self.volumes.clear();
+    }
+}
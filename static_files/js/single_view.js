@@ -317,16 +317,25 @@ function reset_display() {
     display_mode.value = "";
 }
 
+function fetch_scan(url) {
+    return fetch(url, { headers: { 'Accept': 'application/octet-stream' } }).then(resp => resp.arrayBuffer())
+    .then(buffer => {
+        const header_length = new DataView(buffer).getUint32(0, true);
+        const header = JSON.parse(new TextDecoder().decode(new Uint8Array(buffer, 4, header_length)));
+        const values = new Float32Array(buffer.slice(4 + header_length));
+        const [rows, cols] = header.shape;
+
+        return Array.from({ length: rows }, (_, row) => {
+            return Array.from(values.subarray(row * cols, (row + 1) * cols), value => Number.isNaN(value) ? null : value);
+        });
+    });
+}
+
 function load_d_scan(channel, start, end, new_mode) {
     const normalized = a_scan_rel.checked ? 0 : 1;
 
-    fetch(`/d_scan?c=${channel}&start=${start}&end=${end}&as_decibel=${normalized}`).then(resp => resp.json())
+    fetch_scan(`/d_scan?c=${channel}&start=${start}&end=${end}&as_decibel=${normalized}`)
     .then(d_scan_array => {
-        d_scan_array = d_scan_array.map(row => row.map(value => {
-            let start_time = Number(time[0]);
-            let time_step = Number(time[1]) - start_time;
-            return value * time_step;
-        }));
         plot_2d_data(d_scan_array, "D-Bild", new_mode);
     });
 }
@@ -334,7 +343,7 @@ function load_d_scan(channel, start, end, new_mode) {
 function load_c_scan(channel, start, end, new_mode) {
     const normalized = a_scan_rel.checked ? 0 : 1;
 
-    fetch(`/c_scan?c=${channel}&start=${start}&end=${end}&as_decibel=${normalized}`).then(resp => resp.json())
+    fetch_scan(`/c_scan?c=${channel}&start=${start}&end=${end}&as_decibel=${normalized}`)
     .then(c_scan_array => {
         plot_2d_data(c_scan_array, "C-Bild", new_mode);
     });